@@ -0,0 +1,272 @@
+//! `#[derive(Message)]` for rosserial.
+//!
+//! ```ignore
+//! use rosserial::heapless::String;
+//! use rosserial::msgs::Message;
+//!
+//! #[derive(Message, Default)]
+//! #[ros(name = "my_msgs/Status")]
+//! #[ros(constant = "uint8 MODE_IDLE=0")]
+//! struct Status {
+//!     mode: u8,
+//!     battery: f32,
+//!     #[ros(type = "char[4]")]
+//!     code: [u8; 4],
+//!     label: String<16>,
+//! }
+//! ```
+//!
+//! Fields are encoded in order with `rosserial::msgs::Field`. The md5sum is computed with `rosserial::md5sum!`
+//! from the definition implied by the field types, so it matches a `.msg` file declaring the same constants and
+//! fields:
+//!
+//! | Rust                      | ROS          |
+//! |---------------------------|--------------|
+//! | `bool`, `u8` ... `f64`    | `bool`, `uint8` ... `float64` |
+//! | `heapless::String<N>`     | `string`     |
+//! | `ros::Time`, `ros::Duration` | `time`, `duration` |
+//! | `[T; N]`                  | `T[N]`       |
+//! | `heapless::Vec<T, N>`     | `T[]`        |
+//! | any other type            | nested message, which must implement `rosserial::msgs::Md5Sum` |
+//!
+//! `String`, `Vec`, `Time` and `Duration` are builtins when written bare or qualified by `heapless`/`ros`.
+//! Qualified any other way, e.g. `std_msgs::Time`, they are nested messages.
+//!
+//! `#[ros(type = "...")]` overrides the ROS type of a field, e.g. for `char` and `byte`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, ExprLit, Fields, GenericArgument, Lit, LitStr, Path, PathArguments, Type};
+
+#[proc_macro_derive(Message, attributes(ros))]
+pub fn derive_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct MessageAttributes {
+    name: Option<LitStr>,
+    krate: Path,
+    constants: Vec<String>,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attributes = message_attributes(input)?;
+    let name = attributes.name.ok_or_else(|| {
+        syn::Error::new_spanned(&input.ident, "missing #[ros(name = \"package/Type\")]")
+    })?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => return Err(syn::Error::new_spanned(&input.ident, "#[derive(Message)] needs named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "#[derive(Message)] only supports structs")),
+    };
+
+    let mut definition = attributes.constants;
+    let mut nested = Vec::new();
+    let mut idents = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let ros_type = match field_type_override(field)? {
+            Some(ros_type) => ros_type,
+            None => ros_type(&field.ty, &mut nested)?,
+        };
+
+        definition.push(format!("{} {}", ros_type, ident.unraw()));
+        idents.push(ident);
+    }
+
+    let definition = definition.join("\n");
+    let (nested_names, nested_types): (Vec<_>, Vec<_>) = nested.into_iter().unzip();
+
+    let krate = &attributes.krate;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let encoder = if idents.is_empty() { quote!(_encoder) } else { quote!(encoder) };
+    let buf = if idents.is_empty() { quote!(_buf) } else { quote!(buf) };
+
+    let deserialize = if idents.is_empty() {
+        quote!(0)
+    }
+    else {
+        quote! {
+            use #krate::msgs::Field;
+
+            let mut offset = 0usize;
+            #( offset += self.#idents.decode_field(&buf[offset..]); )*
+            offset as u16
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::msgs::Md5Sum for #ident #ty_generics #where_clause {
+            const MD5: &'static str = #krate::md5sum!(#definition #(, #nested_names => #nested_types)*);
+        }
+
+        impl #impl_generics #krate::msgs::Message for #ident #ty_generics #where_clause {
+            fn encode(&self, #encoder: &mut dyn #krate::msgs::Encoder) {
+                #[allow(unused_imports)]
+                use #krate::msgs::Field;
+
+                #( self.#idents.encode_field(encoder); )*
+            }
+
+            fn deserialize(&mut self, #buf: &[u8]) -> u16 {
+                #deserialize
+            }
+
+            fn name() -> &'static str {
+                #name
+            }
+
+            fn md5() -> &'static str {
+                <Self as #krate::msgs::Md5Sum>::MD5
+            }
+        }
+    })
+}
+
+fn message_attributes(input: &DeriveInput) -> syn::Result<MessageAttributes> {
+    let mut attributes = MessageAttributes {
+        name: None,
+        krate: syn::parse_quote!(::rosserial),
+        constants: Vec::new(),
+    };
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("ros")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                attributes.name = Some(meta.value()?.parse()?);
+            }
+            else if meta.path.is_ident("crate") {
+                let path: LitStr = meta.value()?.parse()?;
+                attributes.krate = path.parse()?;
+            }
+            else if meta.path.is_ident("constant") {
+                let constant: LitStr = meta.value()?.parse()?;
+                attributes.constants.push(normalize_constant(&constant)?);
+            }
+            else {
+                return Err(meta.error("expected `name`, `crate` or `constant`"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(attributes)
+}
+
+/// Bring a constant declaration into the form used for md5sums, `type NAME=value`
+fn normalize_constant(constant: &LitStr) -> syn::Result<String> {
+    let text = constant.value();
+    let error = || syn::Error::new_spanned(constant, "expected a constant declaration like \"uint8 NAME=1\"");
+
+    let (declaration, value) = text.split_at(text.find('=').ok_or_else(error)?);
+    let mut words = declaration.split_whitespace();
+    let (ros_type, name) = (words.next().ok_or_else(error)?, words.next().ok_or_else(error)?);
+    if words.next().is_some() {
+        return Err(error());
+    }
+
+    Ok(format!("{} {}={}", ros_type, name, value[1..].trim()))
+}
+
+fn field_type_override(field: &syn::Field) -> syn::Result<Option<String>> {
+    let mut ros_type = None;
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("ros")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type") {
+                let value: LitStr = meta.value()?.parse()?;
+                ros_type = Some(value.value());
+                Ok(())
+            }
+            else {
+                Err(meta.error("expected `type`"))
+            }
+        })?;
+    }
+
+    Ok(ros_type)
+}
+
+/// The ROS type implied by a Rust field type. Nested message types are named by their Rust type and collected
+/// into `nested`, for `md5sum!` to look up their md5sums
+fn ros_type(ty: &Type, nested: &mut Vec<(String, Type)>) -> syn::Result<String> {
+    match ty {
+        Type::Array(array) => {
+            let len = match &array.len {
+                Expr::Lit(ExprLit { lit: Lit::Int(len), .. }) => len.base10_parse::<usize>()?,
+                len => return Err(syn::Error::new_spanned(len, "array length must be an integer literal")),
+            };
+
+            Ok(format!("{}[{}]", ros_type(&array.elem, nested)?, len))
+        },
+        Type::Group(group) => ros_type(&group.elem, nested),
+        Type::Paren(paren) => ros_type(&paren.elem, nested),
+        Type::Path(path) if path.qself.is_none() => {
+            let segments = &path.path.segments;
+            let segment = segments.last().unwrap();
+
+            // Builtins are bare names or qualified by the module providing them, so that e.g.
+            // `std_msgs::Time` is still hashed as a nested message
+            let module = segments.iter().rev().nth(1).map(|s| s.ident.to_string());
+            let builtin = match (module.as_deref(), segment.ident.to_string().as_str()) {
+                (None, "bool") => Some("bool"),
+                (None, "i8") => Some("int8"),
+                (None, "u8") => Some("uint8"),
+                (None, "i16") => Some("int16"),
+                (None, "u16") => Some("uint16"),
+                (None, "i32") => Some("int32"),
+                (None, "u32") => Some("uint32"),
+                (None, "i64") => Some("int64"),
+                (None, "u64") => Some("uint64"),
+                (None, "f32") => Some("float32"),
+                (None, "f64") => Some("float64"),
+                (None, "String") | (Some("heapless"), "String") => Some("string"),
+                (None, "Time") | (Some("ros"), "Time") => Some("time"),
+                (None, "Duration") | (Some("ros"), "Duration") => Some("duration"),
+                (None, "Vec") | (Some("heapless"), "Vec") => {
+                    return Ok(format!("{}[]", ros_type(first_type_argument(ty, &segment.arguments)?, nested)?));
+                },
+                _ => None,
+            };
+
+            match builtin {
+                Some(builtin) => Ok(builtin.to_string()),
+                None => {
+                    let name: String = quote!(#ty).to_string().split_whitespace().collect();
+                    if !nested.iter().any(|(n, _)| *n == name) {
+                        nested.push((name.clone(), ty.clone()));
+                    }
+                    Ok(name)
+                },
+            }
+        },
+        _ => Err(syn::Error::new_spanned(ty, "unsupported field type")),
+    }
+}
+
+fn first_type_argument<'t>(ty: &Type, arguments: &'t PathArguments) -> syn::Result<&'t Type> {
+    if let PathArguments::AngleBracketed(arguments) = arguments {
+        for argument in &arguments.args {
+            if let GenericArgument::Type(ty) = argument {
+                return Ok(ty);
+            }
+        }
+    }
+
+    Err(syn::Error::new_spanned(ty, "expected an element type"))
+}
@@ -0,0 +1,111 @@
+use rosserial::heapless::{String, Vec};
+use rosserial::msgs::{std_msgs, Message as _};
+use rosserial::ros;
+use rosserial_derive::Message;
+
+#[derive(Message, Default, Debug, PartialEq)]
+#[ros(name = "geometry_msgs/Point")]
+struct Point {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Message, Default, Debug, PartialEq)]
+#[ros(name = "geometry_msgs/Quaternion")]
+struct Quaternion {
+    x: f64,
+    y: f64,
+    z: f64,
+    w: f64,
+}
+
+#[derive(Message, Default, Debug, PartialEq)]
+#[ros(name = "geometry_msgs/Pose")]
+struct Pose {
+    position: Point,
+    orientation: Quaternion,
+}
+
+#[derive(Message, Default, Debug, PartialEq)]
+#[ros(name = "geometry_msgs/PoseStamped")]
+struct PoseStamped {
+    header: std_msgs::Header,
+    pose: Pose,
+}
+
+#[derive(Message, Default, Debug, PartialEq)]
+#[ros(name = "test_msgs/Builtins")]
+struct Builtins {
+    stamp: ros::Time,
+    s: String<8>,
+}
+
+#[derive(Message, Default, Debug, PartialEq)]
+#[ros(name = "test_msgs/Stamp")]
+struct Stamp {
+    stamp: std_msgs::Time,
+    s: std_msgs::String,
+}
+
+#[derive(Message, Default, Debug, PartialEq)]
+#[ros(name = "test_msgs/Sample")]
+#[ros(constant = "uint8 MODE_A = 1")]
+struct Sample {
+    #[ros(type = "char[2]")]
+    code: [u8; 2],
+    values: Vec<i16, 4>,
+    r#type: bool,
+}
+
+#[derive(Message, Default)]
+#[ros(name = "std_msgs/Empty")]
+struct Empty {}
+
+#[test]
+fn md5_of_builtin_fields() {
+    assert_eq!(Point::md5(), "4a842b65f413084dc2b10fb484ea7f17");
+    assert_eq!(Empty::md5(), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(Builtins::md5(), "24831e2c00c12b2bba041834a75e977a");
+    assert_eq!(Sample::md5(), "85eaf0970a0e5bff90210d82f318e5ae");
+}
+
+#[test]
+fn md5_of_nested_fields() {
+    assert_eq!(Pose::md5(), "e45d45a5a1ce597b249e23fb30fc871f");
+    assert_eq!(PoseStamped::md5(), "d3812c3cbc69362b77dc0b19b345f8f5");
+}
+
+#[test]
+fn md5_of_std_msgs_named_like_builtins() {
+    // std_msgs::Time and std_msgs::String are messages wrapping a builtin, not the builtin itself
+    assert_eq!(Stamp::md5(), "5d64ada0acc1a03c6d7483e4aab3b4ab");
+    assert_ne!(Stamp::md5(), Builtins::md5());
+}
+
+#[test]
+fn round_trip() {
+    let mut sample = Sample {
+        code: [b'a', b'b'],
+        r#type: true,
+        ..Default::default()
+    };
+    sample.values.push(-3).unwrap();
+
+    let mut buf = [0u8; 128];
+    let len = sample.serialize(&mut buf);
+    assert_eq!(len as usize, 2 + 4 + 2 + 1);
+
+    let mut decoded = Sample::default();
+    assert_eq!(decoded.deserialize(&buf[..len as usize]), len);
+    assert_eq!(decoded, sample);
+
+    let mut pose = PoseStamped::default();
+    pose.header.seq = 7;
+    pose.pose.position.x = 1.5;
+
+    let len = pose.serialize(&mut buf);
+    let mut decoded = PoseStamped::default();
+    assert_eq!(decoded.deserialize(&buf[..len as usize]), len);
+    assert_eq!(decoded, pose);
+}
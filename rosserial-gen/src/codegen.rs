@@ -0,0 +1,266 @@
+//! Rust source generation for one message
+
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use crate::spec::{Array, ConstantSpec, FieldSpec, MsgSpec};
+use crate::{Error, Generator};
+
+/// `Default` is only derived for arrays up to this length
+const MAX_DERIVED_ARRAY: usize = 32;
+
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "else", "enum", "extern", "false", "fn", "for", "if",
+    "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "type", "unsafe", "use", "where", "while", "async", "await", "dyn",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "typeof", "unsized", "virtual",
+    "yield", "try",
+];
+
+/// Keywords that cannot be raw identifiers either, so get a trailing underscore instead
+const PATH_KEYWORDS: &[&str] = &["self", "Self", "super", "crate"];
+
+pub(crate) fn message(generator: &Generator, spec: &MsgSpec) -> Result<String, Error> {
+    let krate = &generator.crate_path;
+    let fields: Vec<(String, String)> = spec.fields.iter()
+        .map(|f| (field_name(&f.name), field_type(generator, spec, f)))
+        .collect();
+
+    let derive_default = spec.fields.iter().all(|f| match f.array {
+        Array::Fixed(n) => n <= MAX_DERIVED_ARRAY,
+        _ => true,
+    });
+
+    let mut out = String::new();
+
+    if derive_default {
+        writeln!(out, "#[derive(Default, Clone, Debug, PartialEq)]").unwrap();
+    }
+    else {
+        writeln!(out, "#[derive(Clone, Debug, PartialEq)]").unwrap();
+    }
+
+    if fields.is_empty() {
+        writeln!(out, "pub struct {} {{}}", spec.name).unwrap();
+    }
+    else {
+        writeln!(out, "pub struct {} {{", spec.name).unwrap();
+        for (name, ty) in &fields {
+            writeln!(out, "    pub {}: {},", name, ty).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+
+    if !spec.constants.is_empty() {
+        writeln!(out, "\nimpl {} {{", spec.name).unwrap();
+        for constant in &spec.constants {
+            writeln!(out, "    pub const {}: {} = {};", field_name(&constant.name), constant_type(&constant.base_type), constant_value(spec, constant)?).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+
+    if !derive_default {
+        writeln!(out, "\nimpl Default for {} {{", spec.name).unwrap();
+        writeln!(out, "    fn default() -> Self {{").unwrap();
+        writeln!(out, "        {} {{", spec.name).unwrap();
+        for (field, (name, _)) in spec.fields.iter().zip(&fields) {
+            match field.array {
+                Array::Fixed(n) if n > MAX_DERIVED_ARRAY => {
+                    writeln!(out, "            {}: core::array::from_fn(|_| Default::default()),", name).unwrap();
+                },
+                _ => writeln!(out, "            {}: Default::default(),", name).unwrap(),
+            }
+        }
+        writeln!(out, "        }}").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+
+    let encoder = if fields.is_empty() { "_encoder" } else { "encoder" };
+    let buf = if fields.is_empty() { "_buf" } else { "buf" };
+
+    writeln!(out, "\nimpl {}::msgs::Message for {} {{", krate, spec.name).unwrap();
+
+    writeln!(out, "    fn encode(&self, {}: &mut dyn {}::msgs::Encoder) {{", encoder, krate).unwrap();
+    if !fields.is_empty() {
+        writeln!(out, "        use {}::msgs::Field;\n", krate).unwrap();
+        for (name, _) in &fields {
+            writeln!(out, "        self.{}.encode_field(encoder);", name).unwrap();
+        }
+    }
+    writeln!(out, "    }}\n").unwrap();
+
+    writeln!(out, "    fn deserialize(&mut self, {}: &[u8]) -> u16 {{", buf).unwrap();
+    if fields.is_empty() {
+        writeln!(out, "        0").unwrap();
+    }
+    else {
+        writeln!(out, "        use {}::msgs::Field;\n", krate).unwrap();
+        writeln!(out, "        let mut offset = 0;").unwrap();
+        for (name, _) in &fields {
+            writeln!(out, "        offset += self.{}.decode_field(&buf[offset..]);", name).unwrap();
+        }
+        writeln!(out, "        offset as u16").unwrap();
+    }
+    writeln!(out, "    }}\n").unwrap();
+
+    writeln!(out, "    fn name() -> &'static str {{").unwrap();
+    writeln!(out, "        {:?}", spec.full_name()).unwrap();
+    writeln!(out, "    }}\n").unwrap();
+
+    writeln!(out, "    fn md5() -> &'static str {{").unwrap();
+    writeln!(out, "        <Self as {}::msgs::Md5Sum>::MD5", krate).unwrap();
+    writeln!(out, "    }}").unwrap();
+
+    writeln!(out, "}}").unwrap();
+
+    out.push_str(&md5sum(generator, spec)?);
+
+    Ok(out)
+}
+
+/// `Md5Sum` implementation hashing the definition at compile time, with nested types looked up by their full name
+fn md5sum(generator: &Generator, spec: &MsgSpec) -> Result<String, Error> {
+    let krate = &generator.crate_path;
+
+    // Fails early on unknown or recursive nested types, rather than in the generated code
+    generator.md5sum(&spec.full_name())?;
+
+    let mut lines = Vec::new();
+    let mut nested: Vec<&str> = Vec::new();
+
+    for constant in &spec.constants {
+        lines.push(format!("{} {}={}", constant.base_type, constant.name, constant.value));
+    }
+
+    for field in &spec.fields {
+        if field.is_builtin() {
+            lines.push(format!("{} {}", field.type_text, field.name));
+        }
+        else {
+            let array = &field.type_text[field.type_text.find('[').unwrap_or(field.type_text.len())..];
+            lines.push(format!("{}{} {}", field.base_type, array, field.name));
+
+            if !nested.contains(&field.base_type.as_str()) {
+                nested.push(&field.base_type);
+            }
+        }
+    }
+
+    let mut out = String::new();
+
+    writeln!(out, "\nimpl {}::msgs::Md5Sum for {} {{", krate, spec.name).unwrap();
+    if nested.is_empty() {
+        writeln!(out, "    const MD5: &'static str = {}::md5sum!({:?});", krate, lines.join("\n")).unwrap();
+    }
+    else {
+        writeln!(out, "    const MD5: &'static str = {}::md5sum!(", krate).unwrap();
+        writeln!(out, "        {:?},", lines.join("\n")).unwrap();
+        for name in nested {
+            writeln!(out, "        {:?} => {},", name, generator.type_path(&spec.package, name)).unwrap();
+        }
+        writeln!(out, "    );").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    Ok(out)
+}
+
+fn field_name(name: &str) -> String {
+    if PATH_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    }
+    else if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    }
+    else {
+        name.to_string()
+    }
+}
+
+fn field_type(generator: &Generator, spec: &MsgSpec, field: &FieldSpec) -> String {
+    let krate = &generator.crate_path;
+
+    let element_capacity = match field.array {
+        Array::None => generator.capacity(spec, &field.name, generator.string_capacity),
+        _ => generator.string_capacity,
+    };
+
+    let element = match field.base_type.as_str() {
+        "bool" => "bool".to_string(),
+        "int8" | "byte" => "i8".to_string(),
+        "uint8" | "char" => "u8".to_string(),
+        "int16" => "i16".to_string(),
+        "uint16" => "u16".to_string(),
+        "int32" => "i32".to_string(),
+        "uint32" => "u32".to_string(),
+        "int64" => "i64".to_string(),
+        "uint64" => "u64".to_string(),
+        "float32" => "f32".to_string(),
+        "float64" => "f64".to_string(),
+        "string" => format!("{}::heapless::String<{}>", krate, element_capacity),
+        "time" => format!("{}::ros::Time", krate),
+        "duration" => format!("{}::ros::Duration", krate),
+        nested => generator.type_path(&spec.package, nested),
+    };
+
+    match field.array {
+        Array::None => element,
+        Array::Fixed(n) => format!("[{}; {}]", element, n),
+        Array::Variable => {
+            let capacity = generator.capacity(spec, &field.name, generator.array_capacity);
+            format!("{}::heapless::Vec<{}, {}>", krate, element, capacity)
+        },
+    }
+}
+
+fn constant_type(base_type: &str) -> &'static str {
+    match base_type {
+        "bool" => "bool",
+        "int8" | "byte" => "i8",
+        "uint8" | "char" => "u8",
+        "int16" => "i16",
+        "uint16" => "u16",
+        "int32" => "i32",
+        "uint32" => "u32",
+        "int64" => "i64",
+        "uint64" => "u64",
+        "float32" => "f32",
+        "float64" => "f64",
+        _ => "&str",
+    }
+}
+
+fn constant_value(spec: &MsgSpec, constant: &ConstantSpec) -> Result<String, Error> {
+    let value = constant.value.as_str();
+    let invalid = || Error::InvalidConstant(format!("{}.{}", spec.full_name(), constant.name));
+
+    let literal = match constant_type(&constant.base_type) {
+        "bool" => match value {
+            "True" | "true" | "1" => "true".to_string(),
+            "False" | "false" | "0" => "false".to_string(),
+            _ => return Err(invalid()),
+        },
+        "f32" | "f64" => format!("{:?}", value.parse::<f64>().map_err(|_| invalid())?),
+        "&str" => format!("{:?}", value),
+        ty => {
+            let n: i128 = value.parse().map_err(|_| invalid())?;
+            let fits = match ty {
+                "i8" => i8::try_from(n).is_ok(),
+                "u8" => u8::try_from(n).is_ok(),
+                "i16" => i16::try_from(n).is_ok(),
+                "u16" => u16::try_from(n).is_ok(),
+                "i32" => i32::try_from(n).is_ok(),
+                "u32" => u32::try_from(n).is_ok(),
+                "i64" => i64::try_from(n).is_ok(),
+                _ => u64::try_from(n).is_ok(),
+            };
+            if !fits {
+                return Err(invalid());
+            }
+            n.to_string()
+        },
+    };
+
+    Ok(literal)
+}
@@ -0,0 +1,374 @@
+//! Generate rosserial message types from ROS `.msg` files.
+//!
+//! Each package becomes one Rust source file holding a struct and `Message` implementation per message.
+//! From a build script:
+//!
+//! ```ignore
+//! let mut generator = rosserial_gen::Generator::new();
+//! generator.add_package("my_msgs", "msg")?;
+//! generator.write("my_msgs", std::env::var("OUT_DIR")?)?;
+//! ```
+//!
+//! and in the crate:
+//!
+//! ```ignore
+//! pub mod my_msgs {
+//!     include!(concat!(env!("OUT_DIR"), "/my_msgs.rs"));
+//! }
+//! ```
+//!
+//! Nested messages from other packages must be added too, so their md5sums can be computed. Messages in
+//! `rosserial::msgs`, i.e. `std_msgs` and the common `geometry_msgs`, are known already and refer there.
+//! Other packages that are added but not generated are expected to be sibling modules of the generated one.
+//! Use `module_path` to point elsewhere.
+
+mod codegen;
+mod spec;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use spec::{Array, ConstantSpec, FieldSpec, MsgSpec, ParseError, BUILTIN_TYPES};
+
+/// Capacity of `string` fields and variable length arrays unless set otherwise
+pub const DEFAULT_CAPACITY: usize = 32;
+
+/// Definitions of the `std_msgs` messages provided by `rosserial::msgs::std_msgs`
+const STD_MSGS: &[(&str, &str)] = &[
+    ("Bool", "bool data"),
+    ("Byte", "byte data"),
+    ("Char", "char data"),
+    ("Int8", "int8 data"),
+    ("UInt8", "uint8 data"),
+    ("Int16", "int16 data"),
+    ("UInt16", "uint16 data"),
+    ("Int32", "int32 data"),
+    ("UInt32", "uint32 data"),
+    ("Int64", "int64 data"),
+    ("UInt64", "uint64 data"),
+    ("Float32", "float32 data"),
+    ("Float64", "float64 data"),
+    ("Time", "time data"),
+    ("Duration", "duration data"),
+    ("String", "string data"),
+    ("Empty", ""),
+    ("Header", "uint32 seq\ntime stamp\nstring frame_id"),
+    ("ColorRGBA", "float32 r\nfloat32 g\nfloat32 b\nfloat32 a"),
+    ("MultiArrayDimension", "string label\nuint32 size\nuint32 stride"),
+    ("MultiArrayLayout", "MultiArrayDimension[] dim\nuint32 data_offset"),
+    ("ByteMultiArray", "MultiArrayLayout layout\nbyte[] data"),
+    ("Int8MultiArray", "MultiArrayLayout layout\nint8[] data"),
+    ("UInt8MultiArray", "MultiArrayLayout layout\nuint8[] data"),
+    ("Int16MultiArray", "MultiArrayLayout layout\nint16[] data"),
+    ("UInt16MultiArray", "MultiArrayLayout layout\nuint16[] data"),
+    ("Int32MultiArray", "MultiArrayLayout layout\nint32[] data"),
+    ("UInt32MultiArray", "MultiArrayLayout layout\nuint32[] data"),
+    ("Int64MultiArray", "MultiArrayLayout layout\nint64[] data"),
+    ("UInt64MultiArray", "MultiArrayLayout layout\nuint64[] data"),
+    ("Float32MultiArray", "MultiArrayLayout layout\nfloat32[] data"),
+    ("Float64MultiArray", "MultiArrayLayout layout\nfloat64[] data"),
+];
+
+/// Definitions of the `geometry_msgs` messages provided by `rosserial::msgs::geometry_msgs`
+const GEOMETRY_MSGS: &[(&str, &str)] = &[
+    ("Point", "float64 x\nfloat64 y\nfloat64 z"),
+    ("Point32", "float32 x\nfloat32 y\nfloat32 z"),
+    ("Vector3", "float64 x\nfloat64 y\nfloat64 z"),
+    ("Quaternion", "float64 x\nfloat64 y\nfloat64 z\nfloat64 w"),
+    ("Pose", "Point position\nQuaternion orientation"),
+    ("PoseStamped", "Header header\nPose pose"),
+    ("PoseWithCovariance", "Pose pose\nfloat64[36] covariance"),
+    ("Twist", "Vector3 linear\nVector3 angular"),
+    ("TwistStamped", "Header header\nTwist twist"),
+    ("Accel", "Vector3 linear\nVector3 angular"),
+    ("Wrench", "Vector3 force\nVector3 torque"),
+    ("Transform", "Vector3 translation\nQuaternion rotation"),
+    ("TransformStamped", "Header header\nstring child_frame_id\nTransform transform"),
+    ("Polygon", "Point32[] points"),
+    ("Inertia", "float64 m\nVector3 com\nfloat64 ixx\nfloat64 ixy\nfloat64 ixz\nfloat64 iyy\nfloat64 iyz\nfloat64 izz"),
+];
+
+/// Packages with messages provided by `rosserial::msgs`
+const PROVIDED: &[(&str, &[(&str, &str)])] = &[("std_msgs", STD_MSGS), ("geometry_msgs", GEOMETRY_MSGS)];
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(ParseError),
+    /// A nested message type that was never added
+    UnknownType(String),
+    /// A package with no messages added
+    UnknownPackage(String),
+    /// A message that contains itself
+    Recursive(String),
+    /// A constant value that does not fit its type
+    InvalidConstant(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::UnknownType(name) => write!(f, "unknown message type {}", name),
+            Error::UnknownPackage(name) => write!(f, "no messages in package {}", name),
+            Error::Recursive(name) => write!(f, "message {} contains itself", name),
+            Error::InvalidConstant(name) => write!(f, "invalid value for constant {}", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+pub struct Generator {
+    messages: BTreeMap<String, MsgSpec>,
+    module_paths: HashMap<String, String>,
+    crate_path: String,
+    string_capacity: usize,
+    array_capacity: usize,
+    capacities: HashMap<(String, String), usize>,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator {
+    /// Create a generator knowing the `std_msgs` and `geometry_msgs` messages provided by rosserial
+    pub fn new() -> Self {
+        let mut generator = Generator {
+            messages: BTreeMap::new(),
+            module_paths: HashMap::new(),
+            crate_path: "::rosserial".to_string(),
+            string_capacity: DEFAULT_CAPACITY,
+            array_capacity: DEFAULT_CAPACITY,
+            capacities: HashMap::new(),
+        };
+
+        for (package, messages) in PROVIDED {
+            for (name, definition) in messages.iter() {
+                generator.add_message(package, name, definition)
+                    .expect("builtin message definition");
+            }
+        }
+
+        generator
+    }
+
+    /// Add every `.msg` file in `dir` to `package`
+    pub fn add_package<P: AsRef<Path>>(&mut self, package: &str, dir: P) -> Result<(), Error> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "msg") {
+                paths.push(path);
+            }
+        }
+
+        for path in paths {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+            let text = fs::read_to_string(&path)?;
+            self.add_message(package, &name, &text)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add one message definition
+    pub fn add_message(&mut self, package: &str, name: &str, text: &str) -> Result<(), Error> {
+        let spec = MsgSpec::parse(package, name, text)?;
+        self.messages.insert(spec.full_name(), spec);
+
+        Ok(())
+    }
+
+    /// Rust path of the module holding an already generated `package`, e.g. `crate::msgs::my_msgs`
+    pub fn module_path(&mut self, package: &str, path: &str) {
+        self.module_paths.insert(package.to_string(), path.to_string());
+    }
+
+    /// Rust path of the rosserial crate, `::rosserial` by default
+    pub fn crate_path(&mut self, path: &str) {
+        self.crate_path = path.to_string();
+    }
+
+    /// Capacity of `string` fields
+    pub fn string_capacity(&mut self, capacity: usize) {
+        self.string_capacity = capacity;
+    }
+
+    /// Capacity of variable length arrays
+    pub fn array_capacity(&mut self, capacity: usize) {
+        self.array_capacity = capacity;
+    }
+
+    /// Capacity of one string or variable length array field, e.g. `("my_msgs/Scan", "ranges", 360)`
+    pub fn field_capacity(&mut self, message: &str, field: &str, capacity: usize) {
+        self.capacities.insert((message.to_string(), field.to_string()), capacity);
+    }
+
+    pub fn message(&self, full_name: &str) -> Option<&MsgSpec> {
+        self.messages.get(full_name)
+    }
+
+    /// Compute the md5sum of a message the way `genmsg` does
+    pub fn md5sum(&self, full_name: &str) -> Result<String, Error> {
+        self.md5sum_nested(full_name, &mut Vec::new())
+    }
+
+    /// The text hashed for a message's md5sum: constants, then fields, with nested types replaced by
+    /// their md5sum
+    pub fn md5_text(&self, full_name: &str) -> Result<String, Error> {
+        self.md5_text_nested(full_name, &mut Vec::new())
+    }
+
+    fn md5sum_nested(&self, full_name: &str, parents: &mut Vec<String>) -> Result<String, Error> {
+        let text = self.md5_text_nested(full_name, parents)?;
+        Ok(format!("{:x}", md5::compute(text)))
+    }
+
+    fn md5_text_nested(&self, full_name: &str, parents: &mut Vec<String>) -> Result<String, Error> {
+        let spec = self.messages.get(full_name).ok_or_else(|| Error::UnknownType(full_name.to_string()))?;
+
+        if parents.iter().any(|p| p == full_name) {
+            return Err(Error::Recursive(full_name.to_string()));
+        }
+        parents.push(full_name.to_string());
+
+        let mut lines = Vec::new();
+
+        for constant in &spec.constants {
+            lines.push(format!("{} {}={}", constant.base_type, constant.name, constant.value));
+        }
+
+        for field in &spec.fields {
+            if field.is_builtin() {
+                lines.push(format!("{} {}", field.type_text, field.name));
+            }
+            else {
+                lines.push(format!("{} {}", self.md5sum_nested(&field.base_type, parents)?, field.name));
+            }
+        }
+
+        parents.pop();
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Generate the source for every message in `package`
+    pub fn generate(&self, package: &str) -> Result<String, Error> {
+        let messages: Vec<&MsgSpec> = self.messages.values().filter(|m| m.package == package).collect();
+        if messages.is_empty() {
+            return Err(Error::UnknownPackage(package.to_string()));
+        }
+
+        let mut out = String::from("// Generated by rosserial-gen, do not edit\n");
+
+        for spec in messages {
+            out.push('\n');
+            out.push_str(&codegen::message(self, spec)?);
+        }
+
+        Ok(out)
+    }
+
+    /// Generate `package` into `dir/<package>.rs`, returning the path written
+    pub fn write<P: AsRef<Path>>(&self, package: &str, dir: P) -> Result<PathBuf, Error> {
+        let path = dir.as_ref().join(format!("{}.rs", package));
+        fs::write(&path, self.generate(package)?)?;
+
+        Ok(path)
+    }
+
+    /// Rust path of `full_name` as seen from the module for `package`
+    fn type_path(&self, package: &str, full_name: &str) -> String {
+        let (type_package, name) = full_name.split_at(full_name.find('/').unwrap_or(0));
+        let name = &name[1..];
+
+        if type_package == package {
+            name.to_string()
+        }
+        else if let Some(path) = self.module_paths.get(type_package) {
+            format!("{}::{}", path, name)
+        }
+        else if is_provided(type_package, name) {
+            format!("{}::msgs::{}::{}", self.crate_path, type_package, name)
+        }
+        else {
+            format!("super::{}::{}", type_package, name)
+        }
+    }
+
+    fn capacity(&self, spec: &MsgSpec, field: &str, default: usize) -> usize {
+        self.capacities.get(&(spec.full_name(), field.to_string())).copied().unwrap_or(default)
+    }
+}
+
+/// Whether `package/name` is provided by `rosserial::msgs`
+fn is_provided(package: &str, name: &str) -> bool {
+    PROVIDED.iter().any(|(p, messages)| *p == package && messages.iter().any(|(n, _)| *n == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use rosserial::msgs::{geometry_msgs, std_msgs, Md5Sum};
+
+    use super::*;
+
+    /// md5sum of the `rosserial::msgs` type for `package/name`, as computed by rosserial at compile time
+    fn rosserial_md5(package: &str, name: &str) -> Option<&'static str> {
+        macro_rules! lookup {
+            ($($package:ident: [$($name:ident),* $(,)?]),*) => {
+                match (package, name) {
+                    $($((stringify!($package), stringify!($name)) => Some(<$package::$name>::MD5),)*)*
+                    _ => None,
+                }
+            };
+        }
+
+        lookup! {
+            std_msgs: [
+                Bool, Byte, Char, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64, UInt64, Float32, Float64,
+                Time, Duration, String, Empty, Header, ColorRGBA, MultiArrayDimension, MultiArrayLayout,
+                ByteMultiArray, Int8MultiArray, UInt8MultiArray, Int16MultiArray, UInt16MultiArray,
+                Int32MultiArray, UInt32MultiArray, Int64MultiArray, UInt64MultiArray, Float32MultiArray,
+                Float64MultiArray,
+            ],
+            geometry_msgs: [
+                Point, Point32, Vector3, Quaternion, Pose, PoseStamped, PoseWithCovariance, Twist, TwistStamped,
+                Accel, Wrench, Transform, TransformStamped, Polygon, Inertia,
+            ]
+        }
+    }
+
+    #[test]
+    fn provided_definitions_match_rosserial() {
+        let generator = Generator::new();
+
+        for (package, messages) in PROVIDED {
+            for (name, _) in messages.iter() {
+                let full_name = format!("{}/{}", package, name);
+                let expected = rosserial_md5(package, name).unwrap_or_else(|| panic!("{} is not in rosserial::msgs", full_name));
+
+                assert_eq!(generator.md5sum(&full_name).unwrap(), expected, "md5sum of {}", full_name);
+            }
+        }
+    }
+}
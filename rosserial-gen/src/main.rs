@@ -0,0 +1,85 @@
+//! Command line front end: `rosserial-gen [options] -o OUT_DIR PACKAGE=MSG_DIR...`
+
+use std::env;
+use std::process;
+
+use rosserial_gen::Generator;
+
+const USAGE: &str = "\
+usage: rosserial-gen [options] -o OUT_DIR PACKAGE=MSG_DIR...
+
+Writes OUT_DIR/PACKAGE.rs for each package given.
+
+options:
+    -o OUT_DIR              directory to write to
+    -d PACKAGE=MSG_DIR      add a package for nested types without generating it
+    -m PACKAGE=PATH         Rust module path of an already generated package
+    --crate PATH            Rust path of the rosserial crate (default ::rosserial)
+    --string-capacity N     capacity of string fields (default 32)
+    --array-capacity N      capacity of variable length arrays (default 32)";
+
+fn main() {
+    if let Err(message) = run() {
+        eprintln!("rosserial-gen: {}\n\n{}", message, USAGE);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut generator = Generator::new();
+    let mut out_dir = None;
+    let mut packages = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+
+        match arg.as_str() {
+            "-o" => out_dir = Some(value()?),
+            "-d" => {
+                let (package, dir) = split_pair(&value()?)?;
+                generator.add_package(&package, &dir).map_err(|e| e.to_string())?;
+            },
+            "-m" => {
+                let (package, path) = split_pair(&value()?)?;
+                generator.module_path(&package, &path);
+            },
+            "--crate" => generator.crate_path(&value()?),
+            "--string-capacity" => generator.string_capacity(parse_capacity(&value()?)?),
+            "--array-capacity" => generator.array_capacity(parse_capacity(&value()?)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ => {
+                let (package, dir) = split_pair(&arg)?;
+                generator.add_package(&package, &dir).map_err(|e| e.to_string())?;
+                packages.push(package);
+            },
+        }
+    }
+
+    let out_dir = out_dir.ok_or("no output directory")?;
+    if packages.is_empty() {
+        return Err("no packages to generate".to_string());
+    }
+
+    for package in packages {
+        let path = generator.write(&package, &out_dir).map_err(|e| e.to_string())?;
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+fn split_pair(arg: &str) -> Result<(String, String), String> {
+    match arg.find('=') {
+        Some(index) => Ok((arg[..index].to_string(), arg[index + 1..].to_string())),
+        None => Err(format!("expected PACKAGE=VALUE, got {}", arg)),
+    }
+}
+
+fn parse_capacity(arg: &str) -> Result<usize, String> {
+    arg.parse().map_err(|_| format!("invalid capacity {}", arg))
+}
@@ -0,0 +1,190 @@
+//! Parsing of `.msg` definitions
+
+use std::fmt;
+
+pub use rosserial::msgs::BUILTIN_TYPES;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Array {
+    None,
+    Fixed(usize),
+    Variable,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldSpec {
+    pub name: String,
+    /// Builtin type name, or the full `package/Type` name of a nested message
+    pub base_type: String,
+    pub array: Array,
+    /// The type as written in the definition, e.g. `float64[9]`
+    pub type_text: String,
+}
+
+impl FieldSpec {
+    pub fn is_builtin(&self) -> bool {
+        BUILTIN_TYPES.contains(&self.base_type.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstantSpec {
+    pub name: String,
+    pub base_type: String,
+    /// The value as written in the definition
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MsgSpec {
+    pub package: String,
+    pub name: String,
+    pub fields: Vec<FieldSpec>,
+    pub constants: Vec<ConstantSpec>,
+}
+
+impl MsgSpec {
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.package, self.name)
+    }
+
+    /// Parse the text of `package/name.msg`
+    pub fn parse(package: &str, name: &str, text: &str) -> Result<Self, ParseError> {
+        let mut spec = MsgSpec {
+            package: package.to_string(),
+            name: name.to_string(),
+            fields: Vec::new(),
+            constants: Vec::new(),
+        };
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| ParseError {
+                message: format!("{}/{}.msg line {}: {}", package, name, index + 1, message),
+            };
+
+            let stripped = strip_comment(line).trim();
+            if stripped.is_empty() {
+                continue;
+            }
+
+            let (type_text, rest) = split_word(stripped);
+            let rest = rest.trim();
+
+            if rest.contains('=') {
+                // String constants keep everything after the '=', comment characters included
+                let definition = if type_text == "string" {
+                    line.trim()[type_text.len()..].trim()
+                }
+                else {
+                    rest
+                };
+
+                let (constant_name, value) = definition.split_at(definition.find('=').unwrap_or(0));
+                let constant_name = constant_name.trim();
+                let value = value[1..].trim();
+
+                if !is_identifier(constant_name) {
+                    return Err(error("invalid constant name"));
+                }
+                if !BUILTIN_TYPES.contains(&type_text) || ["time", "duration"].contains(&type_text) {
+                    return Err(error("constants must have a primitive type"));
+                }
+
+                spec.constants.push(ConstantSpec {
+                    name: constant_name.to_string(),
+                    base_type: type_text.to_string(),
+                    value: value.to_string(),
+                });
+
+                continue;
+            }
+
+            if !is_identifier(rest) {
+                return Err(error("invalid field name"));
+            }
+
+            let (base_type, array) = parse_type(type_text).ok_or_else(|| error("invalid field type"))?;
+
+            spec.fields.push(FieldSpec {
+                name: rest.to_string(),
+                base_type: resolve_type(package, base_type),
+                array,
+                type_text: type_text.to_string(),
+            });
+        }
+
+        Ok(spec)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    match s.find(char::is_whitespace) {
+        Some(index) => s.split_at(index),
+        None => (s, ""),
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Split `float64[9]` into its base type and array kind
+fn parse_type(type_text: &str) -> Option<(&str, Array)> {
+    let (base, array) = match type_text.find('[') {
+        Some(index) => {
+            let size = type_text[index + 1..].strip_suffix(']')?;
+            let array = if size.is_empty() {
+                Array::Variable
+            }
+            else {
+                Array::Fixed(size.parse().ok()?)
+            };
+
+            (&type_text[..index], array)
+        },
+        None => (type_text, Array::None),
+    };
+
+    let valid = base.split('/').count() <= 2 && base.split('/').all(is_identifier);
+    if valid {
+        Some((base, array))
+    }
+    else {
+        None
+    }
+}
+
+/// Qualify a nested message type with its package, as `genmsg` does
+fn resolve_type(package: &str, base_type: &str) -> String {
+    if BUILTIN_TYPES.contains(&base_type) || base_type.contains('/') {
+        base_type.to_string()
+    }
+    else if base_type == "Header" {
+        "std_msgs/Header".to_string()
+    }
+    else {
+        format!("{}/{}", package, base_type)
+    }
+}
@@ -0,0 +1,105 @@
+//! Generates `tests/msg/test_msgs` and checks the output against `tests/generated/test_msgs.rs`, which is
+//! compiled in below. Run with `UPDATE_GENERATED=1` to rewrite it after changing the generator.
+
+use std::convert::TryFrom;
+
+use rosserial::heapless;
+use rosserial::msgs::{geometry_msgs, Md5Sum, Message};
+use rosserial_gen::{Array, Generator};
+
+#[allow(dead_code)]
+mod test_msgs {
+    include!("generated/test_msgs.rs");
+}
+
+use test_msgs::{Nested, Sample};
+
+const MSG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/msg/test_msgs");
+const GENERATED: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/generated/test_msgs.rs");
+
+fn generator() -> Generator {
+    let mut generator = Generator::new();
+    generator.add_package("test_msgs", MSG_DIR).unwrap();
+    generator.field_capacity("test_msgs/Nested", "samples", 2);
+    generator
+}
+
+#[test]
+fn generated_source_is_current() {
+    let source = generator().generate("test_msgs").unwrap();
+
+    if std::env::var_os("UPDATE_GENERATED").is_some() {
+        std::fs::write(GENERATED, &source).unwrap();
+    }
+
+    assert_eq!(source, std::fs::read_to_string(GENERATED).unwrap(), "run with UPDATE_GENERATED=1 to update");
+}
+
+#[test]
+fn parse_constants_arrays_and_nested_types() {
+    let generator = generator();
+    let sample = generator.message("test_msgs/Sample").unwrap();
+
+    let constants: Vec<(&str, &str)> = sample.constants.iter().map(|c| (c.name.as_str(), c.value.as_str())).collect();
+    assert_eq!(constants, [("MODE_A", "1"), ("NEG", "-5"), ("RATIO", "0.25"), ("GREETING", "hello # world"), ("FLAG", "True")]);
+
+    let header = &sample.fields[0];
+    assert_eq!((header.base_type.as_str(), header.array), ("std_msgs/Header", Array::None));
+
+    let points = &sample.fields[1];
+    assert_eq!((points.base_type.as_str(), points.array), ("geometry_msgs/Point", Array::Variable));
+
+    let pair = &sample.fields[2];
+    assert_eq!((pair.type_text.as_str(), pair.array), ("geometry_msgs/Point[2]", Array::Fixed(2)));
+
+    let nested = generator.message("test_msgs/Nested").unwrap();
+    assert_eq!(nested.fields[0].base_type, "test_msgs/Sample");
+}
+
+#[test]
+fn md5sums() {
+    let generator = generator();
+
+    assert_eq!(generator.md5sum("test_msgs/Sample").unwrap(), "5dcf2590180ccfcf52a753fbc753aff2");
+    assert_eq!(generator.md5sum("geometry_msgs/PoseStamped").unwrap(), geometry_msgs::PoseStamped::MD5);
+
+    // The generated types compute their md5sums at compile time, independently of the generator
+    assert_eq!(Sample::md5(), generator.md5sum("test_msgs/Sample").unwrap());
+    assert_eq!(Nested::md5(), generator.md5sum("test_msgs/Nested").unwrap());
+}
+
+#[test]
+fn round_trip() {
+    let mut sample = Sample::default();
+    sample.header.seq = 7;
+    sample.header.frame_id.push_str("map").unwrap();
+    sample.points.push(geometry_msgs::Point { x: 1.0, y: 2.0, z: 3.0 }).unwrap();
+    sample.pair[1].z = -1.5;
+    sample.name.push_str("sample").unwrap();
+    sample.tags.push(heapless::String::try_from("a").unwrap()).unwrap();
+    sample.data.extend_from_slice(&[1, 2, 3]).unwrap();
+    sample.c = b'x';
+    sample.b = -2;
+    sample.r#type = i64::MIN;
+
+    let mut nested = Nested::default();
+    nested.samples.push(sample.clone()).unwrap();
+    nested.pose.pose.orientation.w = 1.0;
+    nested.self_ = 0xBEEF;
+    nested.super_ = 0.5;
+    nested.crate_.push_str("rosserial").unwrap();
+    nested.r#type = -1;
+
+    let mut buf = [0u8; 1024];
+    let len = nested.serialize(&mut buf) as usize;
+    assert_eq!(len, nested.serialized_len());
+
+    let mut decoded = Nested::default();
+    assert_eq!(decoded.deserialize(&buf[..len]) as usize, len);
+    assert_eq!(decoded, nested);
+    assert_eq!(decoded.samples[0], sample);
+
+    assert_eq!(Sample::MODE_A, 1);
+    assert_eq!(Sample::GREETING, "hello # world");
+    assert_eq!(Nested::SELF, 2);
+}
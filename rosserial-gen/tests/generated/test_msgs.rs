@@ -0,0 +1,132 @@
+// Generated by rosserial-gen, do not edit
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Nested {
+    pub samples: ::rosserial::heapless::Vec<Sample, 2>,
+    pub pose: ::rosserial::msgs::geometry_msgs::PoseStamped,
+    pub self_: u16,
+    pub super_: f32,
+    pub crate_: ::rosserial::heapless::String<32>,
+    pub r#type: i8,
+}
+
+impl Nested {
+    pub const SELF: u8 = 2;
+}
+
+impl ::rosserial::msgs::Message for Nested {
+    fn encode(&self, encoder: &mut dyn ::rosserial::msgs::Encoder) {
+        use ::rosserial::msgs::Field;
+
+        self.samples.encode_field(encoder);
+        self.pose.encode_field(encoder);
+        self.self_.encode_field(encoder);
+        self.super_.encode_field(encoder);
+        self.crate_.encode_field(encoder);
+        self.r#type.encode_field(encoder);
+    }
+
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        use ::rosserial::msgs::Field;
+
+        let mut offset = 0;
+        offset += self.samples.decode_field(&buf[offset..]);
+        offset += self.pose.decode_field(&buf[offset..]);
+        offset += self.self_.decode_field(&buf[offset..]);
+        offset += self.super_.decode_field(&buf[offset..]);
+        offset += self.crate_.decode_field(&buf[offset..]);
+        offset += self.r#type.decode_field(&buf[offset..]);
+        offset as u16
+    }
+
+    fn name() -> &'static str {
+        "test_msgs/Nested"
+    }
+
+    fn md5() -> &'static str {
+        <Self as ::rosserial::msgs::Md5Sum>::MD5
+    }
+}
+
+impl ::rosserial::msgs::Md5Sum for Nested {
+    const MD5: &'static str = ::rosserial::md5sum!(
+        "uint8 SELF=2\ntest_msgs/Sample[] samples\ngeometry_msgs/PoseStamped pose\nuint16 self\nfloat32 super\nstring crate\nint8 type",
+        "test_msgs/Sample" => Sample,
+        "geometry_msgs/PoseStamped" => ::rosserial::msgs::geometry_msgs::PoseStamped,
+    );
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub header: ::rosserial::msgs::std_msgs::Header,
+    pub points: ::rosserial::heapless::Vec<::rosserial::msgs::geometry_msgs::Point, 32>,
+    pub pair: [::rosserial::msgs::geometry_msgs::Point; 2],
+    pub name: ::rosserial::heapless::String<32>,
+    pub tags: ::rosserial::heapless::Vec<::rosserial::heapless::String<32>, 32>,
+    pub data: ::rosserial::heapless::Vec<u8, 32>,
+    pub stamp: ::rosserial::ros::Time,
+    pub span: ::rosserial::ros::Duration,
+    pub c: u8,
+    pub b: i8,
+    pub r#type: i64,
+}
+
+impl Sample {
+    pub const MODE_A: u8 = 1;
+    pub const NEG: i32 = -5;
+    pub const RATIO: f64 = 0.25;
+    pub const GREETING: &str = "hello # world";
+    pub const FLAG: bool = true;
+}
+
+impl ::rosserial::msgs::Message for Sample {
+    fn encode(&self, encoder: &mut dyn ::rosserial::msgs::Encoder) {
+        use ::rosserial::msgs::Field;
+
+        self.header.encode_field(encoder);
+        self.points.encode_field(encoder);
+        self.pair.encode_field(encoder);
+        self.name.encode_field(encoder);
+        self.tags.encode_field(encoder);
+        self.data.encode_field(encoder);
+        self.stamp.encode_field(encoder);
+        self.span.encode_field(encoder);
+        self.c.encode_field(encoder);
+        self.b.encode_field(encoder);
+        self.r#type.encode_field(encoder);
+    }
+
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        use ::rosserial::msgs::Field;
+
+        let mut offset = 0;
+        offset += self.header.decode_field(&buf[offset..]);
+        offset += self.points.decode_field(&buf[offset..]);
+        offset += self.pair.decode_field(&buf[offset..]);
+        offset += self.name.decode_field(&buf[offset..]);
+        offset += self.tags.decode_field(&buf[offset..]);
+        offset += self.data.decode_field(&buf[offset..]);
+        offset += self.stamp.decode_field(&buf[offset..]);
+        offset += self.span.decode_field(&buf[offset..]);
+        offset += self.c.decode_field(&buf[offset..]);
+        offset += self.b.decode_field(&buf[offset..]);
+        offset += self.r#type.decode_field(&buf[offset..]);
+        offset as u16
+    }
+
+    fn name() -> &'static str {
+        "test_msgs/Sample"
+    }
+
+    fn md5() -> &'static str {
+        <Self as ::rosserial::msgs::Md5Sum>::MD5
+    }
+}
+
+impl ::rosserial::msgs::Md5Sum for Sample {
+    const MD5: &'static str = ::rosserial::md5sum!(
+        "uint8 MODE_A=1\nint32 NEG=-5\nfloat64 RATIO=0.25\nstring GREETING=hello # world\nbool FLAG=True\nstd_msgs/Header header\ngeometry_msgs/Point[] points\ngeometry_msgs/Point[2] pair\nstring name\nstring[] tags\nuint8[] data\ntime stamp\nduration span\nchar c\nbyte b\nint64 type",
+        "std_msgs/Header" => ::rosserial::msgs::std_msgs::Header,
+        "geometry_msgs/Point" => ::rosserial::msgs::geometry_msgs::Point,
+    );
+}
@@ -0,0 +1,319 @@
+//! Host side of the rosserial protocol, for talking to a device from a Linux program without ROS.
+//!
+//! The host requests the device's topics, again whenever the device stops syncing, answers its time sync
+//! requests with the system clock, and passes messages between the device's topics and typed callbacks. Any `HardwareInterface` can be used
+//! as the link, e.g. a `SerialPortHardware` opened on the device's tty.
+//!
+//! ```ignore
+//! let port = SerialPortHardware::open("/dev/ttyACM0", DEFAULT_BAUD_RATE)?;
+//! let mut host = Host::new(port);
+//!
+//! host.subscribe("status", |msg: std_msgs::Bool| println!("status {}", msg.data));
+//!
+//! loop {
+//!     host.spin_once();
+//!     if host.connected() {
+//!         host.publish("led", &std_msgs::Bool { data: true }).ok();
+//!     }
+//! }
+//! ```
+
+use std::boxed::Box;
+use std::string::{String, ToString};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+use crate::msgs::{Message, rosserial_msgs, std_msgs};
+use crate::ros::{self, HardwareInterface, PROTOCOL_VER2, write_frame};
+
+/// Time between topic requests while the device has not announced its topics (ms)
+const REQUEST_INTERVAL: u32 = 1000;
+
+// Same as rosserial_python, which allows three of rosserial_client's 5 second sync periods
+const DEFAULT_SYNC_TIMEOUT: u32 = 15_000;
+
+#[derive(Debug)]
+pub enum HostError {
+    /// The device has not announced a subscriber on the topic
+    UnknownTopic,
+    /// The device announced the topic with a different message type
+    TypeMismatch,
+}
+
+/// A topic announced by the device
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceTopic {
+    pub id: u16,
+    pub name: String,
+    pub message_type: String,
+    pub md5: String,
+    pub buffer_size: u32,
+}
+
+impl From<rosserial_msgs::TopicInfo<'_>> for DeviceTopic {
+    fn from(info: rosserial_msgs::TopicInfo<'_>) -> Self {
+        DeviceTopic {
+            id: info.id,
+            name: info.name.to_string(),
+            message_type: info.message_type.to_string(),
+            md5: info.md5.to_string(),
+            buffer_size: info.buffer_size,
+        }
+    }
+}
+
+type SubscriptionCallback = Box<dyn FnMut(&[u8])>;
+type LogCallback = Box<dyn FnMut(u8, &str)>;
+
+struct Subscription {
+    topic: String,
+    md5: &'static str,
+    callback: SubscriptionCallback,
+}
+
+/// Rosserial host connected to one device over `H`
+pub struct Host<H> {
+    hardware: H,
+
+    rx: Vec<u8>,
+    last_request: Option<u32>,
+    /// Time of the device's last time sync request, or of the last topic request
+    last_sync: u32,
+    sync_timeout: u32,
+
+    publishers: Vec<DeviceTopic>,
+    subscribers: Vec<DeviceTopic>,
+    subscriptions: Vec<Subscription>,
+    log_callback: Option<LogCallback>,
+}
+
+impl<H: HardwareInterface> Host<H> {
+    pub fn new(hardware: H) -> Self {
+        Host {
+            hardware,
+
+            rx: Vec::new(),
+            last_request: None,
+            last_sync: 0,
+            sync_timeout: DEFAULT_SYNC_TIMEOUT,
+
+            publishers: Vec::new(),
+            subscribers: Vec::new(),
+            subscriptions: Vec::new(),
+            log_callback: None,
+        }
+    }
+
+    pub fn hardware(&mut self) -> &mut H {
+        &mut self.hardware
+    }
+
+    pub fn free(self) -> H {
+        self.hardware
+    }
+
+    /// Whether the device has announced its topics. Cleared when the device stops requesting time sync,
+    /// as it does after a reset, and the topics are requested again
+    pub fn connected(&self) -> bool {
+        !self.publishers.is_empty() || !self.subscribers.is_empty()
+    }
+
+    /// Topics the device publishes
+    pub fn publishers(&self) -> &[DeviceTopic] {
+        &self.publishers
+    }
+
+    /// Topics the device subscribes to
+    pub fn subscribers(&self) -> &[DeviceTopic] {
+        &self.subscribers
+    }
+
+    /// Time (ms) allowed between the device's time sync requests before its topics are requested again
+    pub fn set_sync_timeout(&mut self, timeout: u32) {
+        self.sync_timeout = timeout;
+    }
+
+    /// Ask the device to announce its topics again. Done automatically until it answers, and whenever
+    /// it stops syncing time
+    pub fn request_topics(&mut self) {
+        let now = self.hardware.time();
+
+        self.publishers.clear();
+        self.subscribers.clear();
+        self.last_request = Some(now);
+        self.last_sync = now;
+
+        self.send(rosserial_msgs::TOPICINFO_ID_PUBLISHER, &std_msgs::Empty);
+    }
+
+    /// Tell the device the host is going away
+    pub fn stop(&mut self) {
+        self.publishers.clear();
+        self.subscribers.clear();
+
+        self.send(rosserial_msgs::TOPICINFO_ID_TX_STOP, &std_msgs::Empty);
+    }
+
+    /// Call `callback` with each message the device publishes on `topic`.
+    /// Messages are dropped if the device announced the topic with a different type.
+    pub fn subscribe<Msg, F>(&mut self, topic: &str, mut callback: F)
+    where
+        Msg: Message + Default,
+        F: FnMut(Msg) + 'static,
+    {
+        self.subscriptions.push(Subscription {
+            topic: topic.to_string(),
+            md5: Msg::md5(),
+            callback: Box::new(move |payload| {
+                let mut msg = Msg::default();
+                msg.deserialize(payload);
+                callback(msg);
+            }),
+        });
+    }
+
+    /// Call `callback` with the level and text of each log line from the device
+    pub fn set_log_callback<F: FnMut(u8, &str) + 'static>(&mut self, callback: F) {
+        self.log_callback = Some(Box::new(callback));
+    }
+
+    /// Send `msg` to the device's subscriber on `topic`
+    pub fn publish<Msg: Message>(&mut self, topic: &str, msg: &Msg) -> Result<(), HostError> {
+        let subscriber = self.subscribers.iter()
+            .find(|s| s.name == topic)
+            .ok_or(HostError::UnknownTopic)?;
+
+        if subscriber.md5 != Msg::md5() {
+            return Err(HostError::TypeMismatch);
+        }
+
+        let id = subscriber.id;
+        self.send(id, msg);
+
+        Ok(())
+    }
+
+    /// Read and handle everything the device has sent
+    pub fn spin_once(&mut self) {
+        let now = self.hardware.time();
+        let due = if self.connected() {
+            // A device that lost sync or was reset waits for the host to request its topics
+            now.wrapping_sub(self.last_sync) > self.sync_timeout
+        }
+        else {
+            match self.last_request {
+                Some(t) => now.wrapping_sub(t) >= REQUEST_INTERVAL,
+                None => true,
+            }
+        };
+
+        if due {
+            self.request_topics();
+        }
+
+        let mut buf = [0u8; 256];
+        loop {
+            let n = self.hardware.read_into(&mut buf);
+            if n == 0 {
+                break;
+            }
+            self.rx.extend_from_slice(&buf[..n]);
+        }
+
+        while let Some((topic, payload)) = take_frame(&mut self.rx) {
+            self.handle_frame(topic, &payload);
+        }
+    }
+
+    fn handle_frame(&mut self, topic: u16, payload: &[u8]) {
+        match topic {
+            rosserial_msgs::TOPICINFO_ID_PUBLISHER | rosserial_msgs::TOPICINFO_ID_SUBSCRIBER => {
+                let device_topic = match rosserial_msgs::TopicInfo::parse(payload) {
+                    Some(info) => DeviceTopic::from(info),
+                    None => return,
+                };
+
+                let topics = if topic == rosserial_msgs::TOPICINFO_ID_PUBLISHER {
+                    &mut self.publishers
+                }
+                else {
+                    &mut self.subscribers
+                };
+
+                match topics.iter_mut().find(|t| t.id == device_topic.id) {
+                    Some(t) => *t = device_topic,
+                    None => topics.push(device_topic),
+                }
+            },
+            rosserial_msgs::TOPICINFO_ID_TIME => {
+                self.last_sync = self.hardware.time();
+
+                let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                let time = std_msgs::Time {
+                    data: ros::Time::new(since_epoch.as_secs() as u32, since_epoch.subsec_nanos()),
+                };
+
+                self.send(rosserial_msgs::TOPICINFO_ID_TIME, &time);
+            },
+            rosserial_msgs::TOPICINFO_ID_LOG => {
+                if let (Some(callback), Some(log)) = (self.log_callback.as_mut(), rosserial_msgs::Log::parse(payload)) {
+                    callback(log.level, log.msg);
+                }
+            },
+            _ => {
+                let publisher = match self.publishers.iter().find(|p| p.id == topic) {
+                    Some(p) => p,
+                    None => return,
+                };
+
+                for subscription in self.subscriptions.iter_mut() {
+                    if subscription.topic == publisher.name && subscription.md5 == publisher.md5 {
+                        (subscription.callback)(payload);
+                    }
+                }
+            },
+        }
+    }
+
+    fn send(&mut self, topic: u16, msg: &dyn Message) {
+        write_frame(topic, msg, msg.serialized_len() as u16, &mut self.hardware);
+    }
+}
+
+/// Take the next valid frame off the front of `rx` as its topic id and payload, skipping anything malformed.
+/// Returns `None` once `rx` holds no complete frame, leaving any partial frame in place
+pub(crate) fn take_frame(rx: &mut Vec<u8>) -> Option<(u16, Vec<u8>)> {
+    loop {
+        match rx.iter().position(|b| *b == 0xFF) {
+            Some(start) => { rx.drain(..start); },
+            None => {
+                rx.clear();
+                return None;
+            },
+        }
+
+        if rx.len() < 5 {
+            return None;
+        }
+
+        let (len_lsb, len_msb) = (rx[2], rx[3]);
+        if rx[1] != PROTOCOL_VER2 || 255 - len_lsb.wrapping_add(len_msb) != rx[4] {
+            rx.remove(0);
+            continue;
+        }
+
+        let len = u16::from_le_bytes([len_lsb, len_msb]) as usize;
+        if rx.len() < len + 8 {
+            return None;
+        }
+
+        let checksum = rx[5..7 + len].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if 255 - checksum != rx[7 + len] {
+            rx.remove(0);
+            continue;
+        }
+
+        let frame: Vec<u8> = rx.drain(..len + 8).collect();
+        return Some((u16::from_le_bytes([frame[5], frame[6]]), frame[7..7 + len].to_vec()));
+    }
+}
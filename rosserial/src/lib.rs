@@ -1,4 +1,17 @@
 #![no_std]
-#![feature(const_in_array_repeat_expressions)]
+
+#[cfg(feature = "std")]
+extern crate std;
+
 pub mod ros;
 pub mod msgs;
+pub mod transport;
+
+// Storage for variable length message fields, re-exported for generated code
+pub use heapless;
+
+#[cfg(feature = "host")]
+pub mod host;
+
+#[cfg(feature = "testing")]
+pub mod testing;
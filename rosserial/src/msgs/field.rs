@@ -0,0 +1,176 @@
+use heapless::{String, Vec};
+
+use super::{Encoder, Message};
+use crate::ros;
+
+/// A value that can appear as a message field: the ROS primitive types, time and duration, fixed and
+/// variable length arrays, strings and nested messages. Generated messages are built from these.
+pub trait Field {
+    fn encode_field(&self, encoder: &mut dyn Encoder);
+    /// Decode from the front of `buf`, returning the number of bytes used, which is never more than
+    /// `buf.len()`. Missing bytes read as zero, so a truncated buffer does not panic.
+    fn decode_field(&mut self, buf: &[u8]) -> usize;
+}
+
+macro_rules! impl_field_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl Field for $t {
+                fn encode_field(&self, encoder: &mut dyn Encoder) {
+                    encoder.write(&self.to_le_bytes());
+                }
+
+                fn decode_field(&mut self, buf: &[u8]) -> usize {
+                    let mut bytes = [0u8; core::mem::size_of::<$t>()];
+                    let n = bytes.len().min(buf.len());
+                    bytes[..n].copy_from_slice(&buf[..n]);
+
+                    *self = <$t>::from_le_bytes(bytes);
+                    n
+                }
+            }
+        )*
+    };
+}
+
+impl_field_primitive!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl Field for bool {
+    fn encode_field(&self, encoder: &mut dyn Encoder) {
+        encoder.write(&[*self as u8]);
+    }
+
+    fn decode_field(&mut self, buf: &[u8]) -> usize {
+        let mut data = 0u8;
+        let n = data.decode_field(buf);
+        *self = data != 0;
+        n
+    }
+}
+
+impl Field for ros::Time {
+    fn encode_field(&self, encoder: &mut dyn Encoder) {
+        self.sec.encode_field(encoder);
+        self.nsec.encode_field(encoder);
+    }
+
+    fn decode_field(&mut self, buf: &[u8]) -> usize {
+        let n = self.sec.decode_field(buf);
+        n + self.nsec.decode_field(&buf[n..])
+    }
+}
+
+impl Field for ros::Duration {
+    fn encode_field(&self, encoder: &mut dyn Encoder) {
+        self.sec.encode_field(encoder);
+        self.nsec.encode_field(encoder);
+    }
+
+    fn decode_field(&mut self, buf: &[u8]) -> usize {
+        let n = self.sec.decode_field(buf);
+        n + self.nsec.decode_field(&buf[n..])
+    }
+}
+
+impl<M: Message> Field for M {
+    fn encode_field(&self, encoder: &mut dyn Encoder) {
+        self.encode(encoder);
+    }
+
+    fn decode_field(&mut self, buf: &[u8]) -> usize {
+        (self.deserialize(buf) as usize).min(buf.len())
+    }
+}
+
+/// Fixed length array, encoded without a length prefix
+impl<T: Field, const N: usize> Field for [T; N] {
+    fn encode_field(&self, encoder: &mut dyn Encoder) {
+        for value in self {
+            value.encode_field(encoder);
+        }
+    }
+
+    fn decode_field(&mut self, buf: &[u8]) -> usize {
+        let mut offset = 0;
+
+        for value in self.iter_mut() {
+            offset += value.decode_field(&buf[offset..]);
+        }
+
+        offset
+    }
+}
+
+/// Variable length array, encoded with a u32 length prefix. Elements past the capacity are dropped
+impl<T: Field + Default, const N: usize> Field for Vec<T, N> {
+    fn encode_field(&self, encoder: &mut dyn Encoder) {
+        (self.len() as u32).encode_field(encoder);
+
+        for value in self {
+            value.encode_field(encoder);
+        }
+    }
+
+    fn decode_field(&mut self, buf: &[u8]) -> usize {
+        let mut count = 0u32;
+        let mut offset = count.decode_field(buf);
+
+        self.clear();
+
+        for _ in 0..count {
+            if offset == buf.len() {
+                break;
+            }
+
+            let mut value = T::default();
+            offset += value.decode_field(&buf[offset..]);
+            self.push(value).ok();
+        }
+
+        offset
+    }
+}
+
+/// String, encoded with a u32 length prefix. Strings longer than the capacity are truncated,
+/// and invalid UTF-8 decodes as an empty string.
+impl<const N: usize> Field for String<N> {
+    fn encode_field(&self, encoder: &mut dyn Encoder) {
+        (self.len() as u32).encode_field(encoder);
+        encoder.write(self.as_bytes());
+    }
+
+    fn decode_field(&mut self, buf: &[u8]) -> usize {
+        let mut len = 0u32;
+        let offset = len.decode_field(buf);
+        let end = buf.len().min(offset.saturating_add(len as usize));
+
+        self.clear();
+
+        if let Ok(s) = core::str::from_utf8(&buf[offset..end]) {
+            for c in s.chars() {
+                if self.push(c).is_err() {
+                    break;
+                }
+            }
+        }
+
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_length_past_the_buffer() {
+        let mut s: String<8> = String::new();
+
+        // A corrupt length prefix must stop at the end of the buffer rather than overflow
+        assert_eq!(s.decode_field(&[0xFF, 0xFF, 0xFF, 0xFF, b'a', b'b']), 6);
+        assert_eq!(s.as_str(), "ab");
+
+        assert_eq!(s.decode_field(&[0xFF, 0xFF, 0xFF, 0xFF]), 4);
+        assert_eq!(s.as_str(), "");
+    }
+}
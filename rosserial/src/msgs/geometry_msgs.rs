@@ -0,0 +1,155 @@
+use heapless::Vec;
+
+use super::std_msgs::{Header, FRAME_ID_CAPACITY};
+use super::{Encoder, Field, Md5Sum, Message};
+use crate::md5sum;
+
+/// Default capacity of `Polygon::points`
+pub const POLYGON_CAPACITY: usize = 16;
+
+/// Messages made of fixed size fields and `Header`s, encoded in field order
+macro_rules! message {
+    ($(
+        $(#[$attr:meta])*
+        $name:ident { $($field:ident: $ty:ty),* $(,)? } = $definition:expr $(, $nested:literal => $nested_ty:ty)*;
+    )*) => {
+        $(
+            $(#[$attr])*
+            pub struct $name {
+                $(pub $field: $ty,)*
+            }
+
+            impl Md5Sum for $name {
+                const MD5: &'static str = md5sum!($definition $(, $nested => $nested_ty)*);
+            }
+
+            impl Message for $name {
+                fn encode(&self, encoder: &mut dyn Encoder) {
+                    $(self.$field.encode_field(encoder);)*
+                }
+
+                fn deserialize(&mut self, buf: &[u8]) -> u16 {
+                    let mut offset = 0;
+                    $(offset += self.$field.decode_field(&buf[offset..]);)*
+                    offset as u16
+                }
+
+                fn name() -> &'static str {
+                    concat!("geometry_msgs/", stringify!($name))
+                }
+
+                fn md5() -> &'static str {
+                    Self::MD5
+                }
+            }
+        )*
+    };
+}
+
+message! {
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Point { x: f64, y: f64, z: f64 } = "float64 x\nfloat64 y\nfloat64 z";
+
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Point32 { x: f32, y: f32, z: f32 } = "float32 x\nfloat32 y\nfloat32 z";
+
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Vector3 { x: f64, y: f64, z: f64 } = "float64 x\nfloat64 y\nfloat64 z";
+
+    /// Orientation, which should be normalized. Note the all zero default is not a valid rotation
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Quaternion { x: f64, y: f64, z: f64, w: f64 } = "float64 x\nfloat64 y\nfloat64 z\nfloat64 w";
+
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Pose { position: Point, orientation: Quaternion } = "Point position\nQuaternion orientation",
+        "Point" => Point, "Quaternion" => Quaternion;
+
+    #[derive(Default, Clone, Debug, PartialEq)]
+    PoseStamped { header: Header, pose: Pose } = "Header header\nPose pose",
+        "Header" => Header, "Pose" => Pose;
+
+    /// Pose with its row-major 6x6 covariance, over x, y, z and rotation about the x, y and z axes
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    PoseWithCovariance { pose: Pose, covariance: [f64; 36] } = "Pose pose\nfloat64[36] covariance",
+        "Pose" => Pose;
+
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Twist { linear: Vector3, angular: Vector3 } = "Vector3 linear\nVector3 angular",
+        "Vector3" => Vector3;
+
+    #[derive(Default, Clone, Debug, PartialEq)]
+    TwistStamped { header: Header, twist: Twist } = "Header header\nTwist twist",
+        "Header" => Header, "Twist" => Twist;
+
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Accel { linear: Vector3, angular: Vector3 } = "Vector3 linear\nVector3 angular",
+        "Vector3" => Vector3;
+
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Wrench { force: Vector3, torque: Vector3 } = "Vector3 force\nVector3 torque",
+        "Vector3" => Vector3;
+
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Transform { translation: Vector3, rotation: Quaternion } = "Vector3 translation\nQuaternion rotation",
+        "Vector3" => Vector3, "Quaternion" => Quaternion;
+
+    /// Transform from `header.frame_id` to `child_frame_id`
+    #[derive(Default, Clone, Debug, PartialEq)]
+    TransformStamped {
+        header: Header,
+        child_frame_id: heapless::String<FRAME_ID_CAPACITY>,
+        transform: Transform,
+    } = "Header header\nstring child_frame_id\nTransform transform",
+        "Header" => Header, "Transform" => Transform;
+
+    /// Mass in kg, center of mass in m and inertia tensor in kg m^2
+    #[derive(Default, Clone, Copy, Debug, PartialEq)]
+    Inertia {
+        m: f64,
+        com: Vector3,
+        ixx: f64,
+        ixy: f64,
+        ixz: f64,
+        iyy: f64,
+        iyz: f64,
+        izz: f64,
+    } = "float64 m\nVector3 com\nfloat64 ixx\nfloat64 ixy\nfloat64 ixz\nfloat64 iyy\nfloat64 iyz\nfloat64 izz",
+        "Vector3" => Vector3;
+}
+
+impl Default for PoseWithCovariance {
+    fn default() -> Self {
+        PoseWithCovariance {
+            pose: Pose::default(),
+            covariance: [0.0; 36],
+        }
+    }
+}
+
+/// Polygon of up to `N` points, the last connected to the first
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Polygon<const N: usize = POLYGON_CAPACITY> {
+    pub points: Vec<Point32, N>,
+}
+
+impl<const N: usize> Md5Sum for Polygon<N> {
+    const MD5: &'static str = md5sum!("Point32[] points", "Point32" => Point32);
+}
+
+impl<const N: usize> Message for Polygon<N> {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        self.points.encode_field(encoder);
+    }
+
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        self.points.decode_field(buf) as u16
+    }
+
+    fn name() -> &'static str {
+        "geometry_msgs/Polygon"
+    }
+
+    fn md5() -> &'static str {
+        Self::MD5
+    }
+}
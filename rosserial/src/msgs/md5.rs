@@ -0,0 +1,414 @@
+//! Compile time md5sums of message definitions.
+//!
+//! `md5sum` canonicalizes a definition the way `genmsg` does before hashing it: comments and blank lines
+//! are dropped, constants are listed before fields as `type NAME=value`, and nested message types are
+//! replaced by their own md5sum. Use it through the `md5sum!` macro.
+
+use super::BUILTIN_TYPES;
+
+/// Compute the md5sum of a message definition, as lowercase hex.
+///
+/// `nested` maps each non-builtin type named in `definition`, exactly as written there without any array
+/// suffix, to that type's md5sum. Panics (failing compilation when evaluated in a const) if a nested type
+/// is missing.
+pub const fn md5sum(definition: &str, nested: &[(&str, &str)]) -> [u8; 32] {
+    let mut md5 = Md5::new();
+    let mut first = true;
+
+    // Constants first, then fields
+    let mut pass = 0;
+    while pass < 2 {
+        let text = definition.as_bytes();
+        let mut line_start = 0;
+
+        while line_start < text.len() {
+            let mut line_end = line_start;
+            while line_end < text.len() && text[line_end] != b'\n' {
+                line_end += 1;
+            }
+
+            let line = Line::parse(text, line_start, line_end);
+            let wanted = match line {
+                Line::Empty => false,
+                Line::Constant { .. } => pass == 0,
+                Line::Field { .. } => pass == 1,
+            };
+
+            if wanted {
+                if !first {
+                    md5.update(b"\n", 0, 1);
+                }
+                first = false;
+
+                match line {
+                    Line::Constant { type_start, type_end, name_start, name_end, value_start, value_end } => {
+                        md5.update(text, type_start, type_end);
+                        md5.update(b" ", 0, 1);
+                        md5.update(text, name_start, name_end);
+                        md5.update(b"=", 0, 1);
+                        md5.update(text, value_start, value_end);
+                    },
+                    Line::Field { type_start, base_end, type_end, name_start, name_end } => {
+                        if is_builtin(text, type_start, base_end) {
+                            md5.update(text, type_start, type_end);
+                        }
+                        else {
+                            let sum = lookup(nested, text, type_start, base_end).as_bytes();
+                            md5.update(sum, 0, sum.len());
+                        }
+                        md5.update(b" ", 0, 1);
+                        md5.update(text, name_start, name_end);
+                    },
+                    Line::Empty => {},
+                }
+            }
+
+            line_start = line_end + 1;
+        }
+
+        pass += 1;
+    }
+
+    to_hex(md5.finish())
+}
+
+/// Ranges of one definition line
+#[derive(Clone, Copy)]
+enum Line {
+    Empty,
+    Constant { type_start: usize, type_end: usize, name_start: usize, name_end: usize, value_start: usize, value_end: usize },
+    Field { type_start: usize, base_end: usize, type_end: usize, name_start: usize, name_end: usize },
+}
+
+impl Line {
+    const fn parse(text: &[u8], start: usize, end: usize) -> Line {
+        // Comments run to the end of the line, except in string constants which are checked below
+        let mut content_end = start;
+        while content_end < end && text[content_end] != b'#' {
+            content_end += 1;
+        }
+
+        let type_start = skip_space(text, start, content_end);
+        if type_start == content_end {
+            return Line::Empty;
+        }
+
+        let type_end = skip_word(text, type_start, content_end);
+
+        let mut base_end = type_start;
+        while base_end < type_end && text[base_end] != b'[' {
+            base_end += 1;
+        }
+
+        let name_start = skip_space(text, type_end, content_end);
+        let mut equals = name_start;
+        while equals < content_end && text[equals] != b'=' {
+            equals += 1;
+        }
+
+        if equals == content_end {
+            let name_end = trim_end(text, name_start, content_end);
+            return Line::Field { type_start, base_end, type_end, name_start, name_end };
+        }
+
+        let name_end = trim_end(text, name_start, equals);
+
+        // String constant values keep everything after the '='
+        let is_string = type_end - type_start == 6 && bytes_eq(text, type_start, type_end, b"string");
+        let value_end = if is_string {
+            trim_end(text, equals + 1, end)
+        }
+        else {
+            trim_end(text, equals + 1, content_end)
+        };
+        let value_start = skip_space(text, equals + 1, value_end);
+
+        Line::Constant { type_start, type_end, name_start, name_end, value_start, value_end }
+    }
+}
+
+const fn is_space(b: u8) -> bool {
+    b == b' ' || b == b'\t' || b == b'\r'
+}
+
+const fn skip_space(text: &[u8], mut start: usize, end: usize) -> usize {
+    while start < end && is_space(text[start]) {
+        start += 1;
+    }
+    start
+}
+
+const fn skip_word(text: &[u8], mut start: usize, end: usize) -> usize {
+    while start < end && !is_space(text[start]) {
+        start += 1;
+    }
+    start
+}
+
+const fn trim_end(text: &[u8], start: usize, mut end: usize) -> usize {
+    while end > start && is_space(text[end - 1]) {
+        end -= 1;
+    }
+    end
+}
+
+const fn bytes_eq(text: &[u8], start: usize, end: usize, other: &[u8]) -> bool {
+    if end - start != other.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < other.len() {
+        if text[start + i] != other[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+const fn is_builtin(text: &[u8], start: usize, end: usize) -> bool {
+    let mut i = 0;
+    while i < BUILTIN_TYPES.len() {
+        if bytes_eq(text, start, end, BUILTIN_TYPES[i].as_bytes()) {
+            return true;
+        }
+        i += 1;
+    }
+
+    false
+}
+
+const fn lookup<'a>(nested: &[(&str, &'a str)], text: &[u8], start: usize, end: usize) -> &'a str {
+    let mut i = 0;
+    while i < nested.len() {
+        if bytes_eq(text, start, end, nested[i].0.as_bytes()) {
+            return nested[i].1;
+        }
+        i += 1;
+    }
+
+    panic!("nested message type missing from md5sum");
+}
+
+const fn to_hex(digest: [u8; 16]) -> [u8; 32] {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    let mut hex = [0u8; 32];
+    let mut i = 0;
+    while i < 16 {
+        hex[i * 2] = DIGITS[(digest[i] >> 4) as usize];
+        hex[i * 2 + 1] = DIGITS[(digest[i] & 0xF) as usize];
+        i += 1;
+    }
+
+    hex
+}
+
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+    5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20,
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+    6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+const K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// Streaming MD5 (RFC 1321), usable in const evaluation
+struct Md5 {
+    state: [u32; 4],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Md5 {
+    const fn new() -> Self {
+        Md5 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Hash `data[start..end]`
+    const fn update(&mut self, data: &[u8], start: usize, end: usize) {
+        let mut i = start;
+        while i < end {
+            self.push(data[i]);
+            i += 1;
+        }
+        self.total_len += (end - start) as u64;
+    }
+
+    const fn push(&mut self, b: u8) {
+        self.block[self.block_len] = b;
+        self.block_len += 1;
+
+        if self.block_len == 64 {
+            self.compress();
+            self.block_len = 0;
+        }
+    }
+
+    const fn finish(mut self) -> [u8; 16] {
+        let bit_len = self.total_len.wrapping_mul(8).to_le_bytes();
+
+        self.push(0x80);
+        while self.block_len != 56 {
+            self.push(0);
+        }
+
+        let mut i = 0;
+        while i < 8 {
+            self.push(bit_len[i]);
+            i += 1;
+        }
+
+        let mut digest = [0u8; 16];
+        let mut i = 0;
+        while i < 4 {
+            let word = self.state[i].to_le_bytes();
+            digest[i * 4] = word[0];
+            digest[i * 4 + 1] = word[1];
+            digest[i * 4 + 2] = word[2];
+            digest[i * 4 + 3] = word[3];
+            i += 1;
+        }
+
+        digest
+    }
+
+    const fn compress(&mut self) {
+        let mut m = [0u32; 16];
+        let mut i = 0;
+        while i < 16 {
+            m[i] = u32::from_le_bytes([self.block[i * 4], self.block[i * 4 + 1], self.block[i * 4 + 2], self.block[i * 4 + 3]]);
+            i += 1;
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+
+        let mut i = 0;
+        while i < 64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+
+            let rotated = a.wrapping_add(f).wrapping_add(K[i]).wrapping_add(m[g]).rotate_left(SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+
+            i += 1;
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+    }
+}
+
+/// md5sum of a message definition as a `&'static str`, computed at compile time.
+///
+/// Nested message types are listed after the definition with the Rust type whose `Md5Sum` gives their
+/// md5sum, so nested definitions are hashed recursively:
+///
+/// ```ignore
+/// impl Md5Sum for PoseStamped {
+///     const MD5: &'static str = md5sum!("Header header\nPose pose", "Header" => std_msgs::Header, "Pose" => Pose);
+/// }
+/// ```
+#[macro_export]
+macro_rules! md5sum {
+    ($definition:expr $(, $name:literal => $nested:ty)* $(,)?) => {{
+        const HEX: [u8; 32] = $crate::msgs::md5::md5sum(
+            $definition,
+            &[$(($name, <$nested as $crate::msgs::Md5Sum>::MD5)),*],
+        );
+        const MD5: &str = match ::core::str::from_utf8(&HEX) {
+            Ok(md5) => md5,
+            Err(_) => panic!("md5sum is not hex"),
+        };
+        MD5
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msgs::{geometry_msgs, rosserial_msgs, std_msgs, Md5Sum};
+
+    fn digest(data: &str) -> [u8; 32] {
+        let mut md5 = Md5::new();
+        md5.update(data.as_bytes(), 0, data.len());
+        to_hex(md5.finish())
+    }
+
+    #[test]
+    fn rfc1321_vectors() {
+        let vectors = [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            ("abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b"),
+            ("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789", "d174ab98d277d9f5a5611c2c9f419d9f"),
+            ("12345678901234567890123456789012345678901234567890123456789012345678901234567890", "57edf4a22be3c955ac49da2e2107b67a"),
+        ];
+
+        for (data, expected) in vectors.iter() {
+            assert_eq!(&digest(data), expected.as_bytes(), "md5 of {:?}", data);
+        }
+    }
+
+    #[test]
+    fn canonical_text() {
+        let plain = md5sum("uint8 A=1\nstring S=a # b\nuint8 level\nstring msg", &[]);
+        let written = md5sum("# Comment\n\nuint8 level  # trailing\n  uint8 A = 1\nstring msg\nstring S=a # b\n", &[]);
+
+        assert_eq!(plain, written);
+        assert_eq!(plain, digest("uint8 A=1\nstring S=a # b\nuint8 level\nstring msg"));
+    }
+
+    #[test]
+    fn ros_messages() {
+        let known = [
+            (std_msgs::Bool::MD5, "8b94c1b53db61fb6aed406028ad6332a"),
+            (std_msgs::Empty::MD5, "d41d8cd98f00b204e9800998ecf8427e"),
+            (std_msgs::String::<8>::MD5, "992ce8a1687cec8c8bd883ec73ca41d1"),
+            (std_msgs::Time::MD5, "cd7166c74c552c311fbcc2fe5a7bc289"),
+            (std_msgs::Header::MD5, "2176decaecbce78abc3b96ef049fabed"),
+            (std_msgs::Float32MultiArray::<8>::MD5, "6a40e0ffa6a17a503ac3f8616991b1f6"),
+            (geometry_msgs::Point::MD5, "4a842b65f413084dc2b10fb484ea7f17"),
+            (geometry_msgs::Quaternion::MD5, "a779879fadf0160734f906b8c19c7004"),
+            (geometry_msgs::Pose::MD5, "e45d45a5a1ce597b249e23fb30fc871f"),
+            (geometry_msgs::PoseStamped::MD5, "d3812c3cbc69362b77dc0b19b345f8f5"),
+            (geometry_msgs::PoseWithCovariance::MD5, "c23e848cf1b7533a8d7c259073a97e6f"),
+            (geometry_msgs::TransformStamped::MD5, "b5764a33bfeb3588febc2682852579b0"),
+            (geometry_msgs::Polygon::<4>::MD5, "cd60a26494a087f577976f0329fa120e"),
+            (rosserial_msgs::TopicInfo::MD5, "0ad51f88fc44892f8c10684077646005"),
+            (rosserial_msgs::Log::MD5, "11abd731c25933261cd6183bd12d6295"),
+        ];
+
+        for (md5, expected) in known.iter() {
+            assert_eq!(md5, expected);
+        }
+    }
+}
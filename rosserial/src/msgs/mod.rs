@@ -1,9 +1,115 @@
 pub mod std_msgs;
+pub mod geometry_msgs;
 pub mod rosserial_msgs;
 
+mod field;
+pub use field::Field;
+
+#[doc(hidden)]
+pub mod md5;
+
+#[cfg(feature = "derive")]
+pub use rosserial_derive::Message;
+
+/// Types built into the message definition language, as opposed to nested messages
+pub const BUILTIN_TYPES: &[&str] = &[
+    "bool", "int8", "uint8", "int16", "uint16", "int32", "uint32", "int64", "uint64",
+    "float32", "float64", "string", "time", "duration", "byte", "char",
+];
+
+/// Sink for serialized message bytes
+pub trait Encoder {
+    fn write(&mut self, data: &[u8]);
+}
+
 pub trait Message {
-    fn serialize(&self, buf: &mut [u8]) -> u16;
+    /// Serialize the message, in order, through `encoder`
+    fn encode(&self, encoder: &mut dyn Encoder);
     fn deserialize(&mut self, buf: &[u8]) -> u16;
     fn name() -> &'static str where Self: Sized;
     fn md5() -> &'static str where Self: Sized;
+
+    /// Serialize into `buf`, returning the number of bytes written. Panics if `buf` is too small
+    fn serialize(&self, buf: &mut [u8]) -> u16 {
+        let mut encoder = SliceEncoder::new(buf);
+        self.encode(&mut encoder);
+        encoder.len() as u16
+    }
+
+    fn serialized_len(&self) -> usize {
+        let mut encoder = LengthEncoder::default();
+        self.encode(&mut encoder);
+        encoder.len()
+    }
+}
+
+/// md5sum of a message known at compile time, so messages nesting it can compute their own with `md5sum!`
+pub trait Md5Sum {
+    const MD5: &'static str;
+}
+
+/// A ROS service, pairing a request and response message
+pub trait Service {
+    type Request: Message + Default;
+    type Response: Message + Default;
+
+    fn name() -> &'static str;
+}
+
+/// Encoder writing into a byte slice
+pub struct SliceEncoder<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> SliceEncoder<'b> {
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        SliceEncoder {
+            buf,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Encoder for SliceEncoder<'_> {
+    fn write(&mut self, data: &[u8]) {
+        self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+        self.len += data.len();
+    }
+}
+
+/// Encoder that only counts the serialized length
+#[derive(Default)]
+pub struct LengthEncoder {
+    len: usize,
+}
+
+impl LengthEncoder {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Encoder for LengthEncoder {
+    fn write(&mut self, data: &[u8]) {
+        self.len += data.len();
+    }
+}
+
+/// Write a string as a little endian u32 length followed by its bytes
+pub(crate) fn encode_str(encoder: &mut dyn Encoder, s: &str) {
+    encoder.write(&(s.len() as u32).to_le_bytes());
+    encoder.write(s.as_bytes());
 }
@@ -1,4 +1,5 @@
-use crate::msgs::Message;
+use crate::md5sum;
+use crate::msgs::{Encoder, Md5Sum, Message, encode_str};
 
 pub const TOPICINFO_ID_PUBLISHER: u16 = 0;
 pub const TOPICINFO_ID_SUBSCRIBER: u16 = 1;
@@ -9,91 +10,297 @@ pub const TOPICINFO_ID_LOG: u16 = 7;
 pub const TOPICINFO_ID_TIME: u16 = 10;
 pub const TOPICINFO_ID_TX_STOP: u16 = 11;
 
+pub const LOG_ROSDEBUG: u8 = 0;
+pub const LOG_INFO: u8 = 1;
+pub const LOG_WARN: u8 = 2;
+pub const LOG_ERROR: u8 = 3;
+pub const LOG_FATAL: u8 = 4;
+
 
 #[derive(Default, Clone, Copy)]
-pub struct TopicInfo {
+pub struct TopicInfo<'a> {
     pub id: u16,
-    pub name: &'static str,
-    pub message_type: &'static str,
-    pub md5: &'static str,
+    pub name: &'a str,
+    pub message_type: &'a str,
+    pub md5: &'a str,
     pub buffer_size: u32,
 }
 
-impl Message for TopicInfo {
-    fn serialize(&self, buf: &mut [u8]) -> u16 {
-        let mut offset = 0;
-
-        buf[offset] = (self.id & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((self.id >> 8) & 0xFF) as u8;
-        offset += 1;
-
-        let length_name: u32 = self.name.len() as u32;
-        buf[offset] = (length_name & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_name >> 8) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_name >> 16) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_name >> 24) & 0xFF) as u8;
-        offset += 1;
-
-        for b in self.name[..].as_bytes().iter() {
-            buf[offset] = *b;
-            offset += 1;
+impl<'a> TopicInfo<'a> {
+    /// Read a serialized `TopicInfo`, borrowing its strings from `buf`. Returns `None` if the buffer is truncated
+    pub fn parse(buf: &'a [u8]) -> Option<Self> {
+        Self::split(buf).map(|(info, _)| info)
+    }
+
+    fn split(buf: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        if buf.len() < 2 {
+            return None;
         }
 
-        let length_message_type = self.message_type.len();
-        buf[offset] = (length_message_type & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_message_type >> 8) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_message_type >> 16) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_message_type >> 24) & 0xFF) as u8;
-        offset += 1;
-
-        for b in self.message_type[..].as_bytes().iter() {
-            buf[offset] = *b;
-            offset += 1;
+        let id = (buf[0] as u16) | ((buf[1] as u16) << 8);
+        let (name, rest) = split_str(&buf[2..])?;
+        let (message_type, rest) = split_str(rest)?;
+        let (md5, rest) = split_str(rest)?;
+        let buffer_size = read_u32(rest)?;
+
+        let info = TopicInfo {
+            id,
+            name,
+            message_type,
+            md5,
+            buffer_size,
+        };
+
+        Some((info, &rest[4..]))
+    }
+}
+
+impl Md5Sum for TopicInfo<'_> {
+    const MD5: &'static str = md5sum!(concat!(
+        "uint16 ID_PUBLISHER=0\n",
+        "uint16 ID_SUBSCRIBER=1\n",
+        "uint16 ID_SERVICE_SERVER=2\n",
+        "uint16 ID_SERVICE_CLIENT=4\n",
+        "uint16 ID_PARAMETER_REQUEST=6\n",
+        "uint16 ID_LOG=7\n",
+        "uint16 ID_TIME=10\n",
+        "uint16 ID_TX_STOP=11\n",
+        "uint16 topic_id\n",
+        "string topic_name\n",
+        "string message_type\n",
+        "string md5sum\n",
+        "int32 buffer_size",
+    ));
+}
+
+impl Message for TopicInfo<'_> {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        encoder.write(&self.id.to_le_bytes());
+        encode_str(encoder, self.name);
+        encode_str(encoder, self.message_type);
+        encode_str(encoder, self.md5);
+        encoder.write(&self.buffer_size.to_le_bytes());
+    }
+
+    /// Reads the id and buffer size. The strings can only borrow from a buffer that outlives `self`, so are
+    /// left empty: use `parse` to read them
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        match TopicInfo::split(buf) {
+            Some((info, rest)) => {
+                *self = TopicInfo {
+                    id: info.id,
+                    buffer_size: info.buffer_size,
+                    ..TopicInfo::default()
+                };
+                (buf.len() - rest.len()) as u16
+            },
+            None => 0,
         }
+    }
+
+    fn name() -> &'static str {
+        "rosserial_msgs/TopicInfo"
+    }
+
+    fn md5() -> &'static str {
+        Self::MD5
+    }
+}
 
-        let length_md5 = self.md5.len();
-        buf[offset] = (length_md5 & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_md5 >> 8) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_md5 >> 16) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((length_md5 >> 24) & 0xFF) as u8;
-        offset += 1;
-
-        for b in self.md5[..].as_bytes().iter() {
-            buf[offset] = *b;
-            offset += 1;
+#[derive(Default)]
+pub struct Log<'a> {
+    pub level: u8,
+    pub msg: &'a str,
+}
+
+impl<'a> Log<'a> {
+    /// Read a serialized `Log`, borrowing its text from `buf`. Returns `None` if the buffer is truncated
+    pub fn parse(buf: &'a [u8]) -> Option<Self> {
+        Self::split(buf).map(|(log, _)| log)
+    }
+
+    fn split(buf: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let (&level, rest) = buf.split_first()?;
+        let (msg, rest) = split_str(rest)?;
+
+        Some((Log { level, msg }, rest))
+    }
+}
+
+impl Md5Sum for Log<'_> {
+    const MD5: &'static str = md5sum!(concat!(
+        "uint8 ROSDEBUG=0\n",
+        "uint8 INFO=1\n",
+        "uint8 WARN=2\n",
+        "uint8 ERROR=3\n",
+        "uint8 FATAL=4\n",
+        "uint8 level\n",
+        "string msg",
+    ));
+}
+
+impl Message for Log<'_> {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        encoder.write(&[self.level]);
+        encode_str(encoder, self.msg);
+    }
+
+    /// Reads the level. The text can only borrow from a buffer that outlives `self`, so is left empty: use
+    /// `parse` to read it
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        match Log::split(buf) {
+            Some((log, rest)) => {
+                *self = Log { level: log.level, msg: "" };
+                (buf.len() - rest.len()) as u16
+            },
+            None => 0,
         }
+    }
+
+    fn name() -> &'static str {
+        "rosserial_msgs/Log"
+    }
+
+    fn md5() -> &'static str {
+        Self::MD5
+    }
+}
 
-        buf[offset] = (self.buffer_size & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((self.buffer_size >> 8) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((self.buffer_size >> 16) & 0xFF) as u8;
-        offset += 1;
-        buf[offset] = ((self.buffer_size >> 24) & 0xFF) as u8;
-        offset += 1;
+/// Request half of the `rosserial_msgs/RequestParam` service
+#[derive(Default)]
+pub struct RequestParamRequest<'a> {
+    pub name: &'a str,
+}
 
-        offset as u16
+impl<'a> RequestParamRequest<'a> {
+    /// Read a serialized request, borrowing the parameter name from `buf`. Returns `None` if the buffer is truncated
+    pub fn parse(buf: &'a [u8]) -> Option<Self> {
+        let (name, _) = split_str(buf)?;
+        Some(RequestParamRequest { name })
     }
+}
+
+impl Md5Sum for RequestParamRequest<'_> {
+    const MD5: &'static str = md5sum!("string name");
+}
 
-    fn deserialize(&mut self, _buf: &[u8]) -> u16 {
-        0
+impl Message for RequestParamRequest<'_> {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        encode_str(encoder, self.name);
+    }
+
+    /// Checks the request is complete. The name can only borrow from a buffer that outlives `self`, so is
+    /// left empty: use `parse` to read it
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        match split_str(buf) {
+            Some((_, rest)) => {
+                self.name = "";
+                (buf.len() - rest.len()) as u16
+            },
+            None => 0,
+        }
     }
 
     fn name() -> &'static str {
-        "rosserial_msgs/TopicInfo"
+        "rosserial_msgs/RequestParam"
     }
 
     fn md5() -> &'static str {
-        "0ad51f88fc44892f8c10684077646005"
+        Self::MD5
+    }
+}
+
+/// Response half of the `rosserial_msgs/RequestParam` service.
+///
+/// The variable length fields are read in place from the received frame rather than copied out.
+pub struct RequestParamResponse<'a> {
+    ints: &'a [u8],
+    floats: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> RequestParamResponse<'a> {
+    pub const MD5: &'static str = md5sum!("int32[] ints\nfloat32[] floats\nstring[] strings");
+
+    /// Split a serialized response into its fields. Returns `None` if the buffer is truncated
+    pub fn parse(buf: &'a [u8]) -> Option<Self> {
+        let (ints, rest) = split_array(buf, 4)?;
+        let (floats, rest) = split_array(rest, 4)?;
+
+        // Strings are variable length, so walk them to check they fit in the buffer
+        let count = read_u32(rest)? as usize;
+        let mut offset = 4;
+        for _ in 0..count {
+            let len = read_u32(&rest[offset..])? as usize;
+            offset = offset.checked_add(4)?.checked_add(len)?;
+            if offset > rest.len() {
+                return None;
+            }
+        }
+
+        Some(RequestParamResponse {
+            ints,
+            floats,
+            strings: &rest[..offset],
+        })
+    }
+
+    pub fn ints(&self) -> impl Iterator<Item = i32> + 'a {
+        self.ints[4..].chunks(4).map(|b| read_u32(b).unwrap_or(0) as i32)
+    }
+
+    pub fn floats(&self) -> impl Iterator<Item = f32> + 'a {
+        self.floats[4..].chunks(4).map(|b| f32::from_bits(read_u32(b).unwrap_or(0)))
     }
-}
\ No newline at end of file
+
+    pub fn strings(&self) -> impl Iterator<Item = &'a str> + 'a {
+        let mut rest = &self.strings[4..];
+        core::iter::from_fn(move || {
+            let len = read_u32(rest)? as usize;
+            let s = core::str::from_utf8(&rest[4..4 + len]).unwrap_or("");
+            rest = &rest[4 + len..];
+            Some(s)
+        })
+    }
+
+    pub fn ints_len(&self) -> usize {
+        (self.ints.len() - 4) / 4
+    }
+
+    pub fn floats_len(&self) -> usize {
+        (self.floats.len() - 4) / 4
+    }
+
+    pub fn strings_len(&self) -> usize {
+        read_u32(self.strings).unwrap_or(0) as usize
+    }
+}
+
+fn read_u32(buf: &[u8]) -> Option<u32> {
+    if buf.len() < 4 {
+        return None;
+    }
+
+    Some((buf[0] as u32)
+         | ((buf[1] as u32) << 8)
+         | ((buf[2] as u32) << 16)
+         | ((buf[3] as u32) << 24))
+}
+
+/// Split a length prefixed string off the front of `buf`
+fn split_str(buf: &[u8]) -> Option<(&str, &[u8])> {
+    let (s, rest) = split_array(buf, 1)?;
+    Some((core::str::from_utf8(&s[4..]).ok()?, rest))
+}
+
+/// Split a length prefixed array of fixed size elements off the front of `buf`
+fn split_array(buf: &[u8], element_size: usize) -> Option<(&[u8], &[u8])> {
+    let count = read_u32(buf)? as usize;
+    let end = count.checked_mul(element_size)?.checked_add(4)?;
+
+    if end > buf.len() {
+        return None;
+    }
+
+    Some(buf.split_at(end))
+}
@@ -1,74 +1,317 @@
-use super::Message;
+use heapless::Vec;
+
+use super::{Encoder, Field, Md5Sum, Message};
+use crate::md5sum;
 use crate::ros;
 
-#[derive(Default)]
-pub struct Bool {
-    pub data: bool,
+/// Capacity of `Header::frame_id`
+pub const FRAME_ID_CAPACITY: usize = 32;
+/// Default capacity of `String::data`
+pub const STRING_CAPACITY: usize = 64;
+/// Default capacity of the `data` of the `*MultiArray` messages
+pub const MULTI_ARRAY_CAPACITY: usize = 32;
+/// Capacity of `MultiArrayLayout::dim`
+pub const MULTI_ARRAY_DIMENSIONS: usize = 4;
+/// Capacity of `MultiArrayDimension::label`
+pub const DIMENSION_LABEL_CAPACITY: usize = 16;
+
+/// Messages wrapping a single fixed size `data` field
+macro_rules! data_message {
+    ($($name:ident($ty:ty) = $definition:literal;)*) => {
+        $(
+            #[derive(Default, Clone, Copy, Debug, PartialEq)]
+            pub struct $name {
+                pub data: $ty,
+            }
+
+            impl Md5Sum for $name {
+                const MD5: &'static str = md5sum!($definition);
+            }
+
+            impl Message for $name {
+                fn encode(&self, encoder: &mut dyn Encoder) {
+                    self.data.encode_field(encoder);
+                }
+
+                fn deserialize(&mut self, buf: &[u8]) -> u16 {
+                    self.data.decode_field(buf) as u16
+                }
+
+                fn name() -> &'static str {
+                    concat!("std_msgs/", stringify!($name))
+                }
+
+                fn md5() -> &'static str {
+                    Self::MD5
+                }
+            }
+        )*
+    };
+}
+
+data_message! {
+    Bool(bool) = "bool data";
+    Byte(i8) = "byte data";
+    Char(u8) = "char data";
+    Int8(i8) = "int8 data";
+    UInt8(u8) = "uint8 data";
+    Int16(i16) = "int16 data";
+    UInt16(u16) = "uint16 data";
+    Int32(i32) = "int32 data";
+    UInt32(u32) = "uint32 data";
+    Int64(i64) = "int64 data";
+    UInt64(u64) = "uint64 data";
+    Float32(f32) = "float32 data";
+    Float64(f64) = "float64 data";
+    Time(ros::Time) = "time data";
+    Duration(ros::Duration) = "duration data";
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct String<const N: usize = STRING_CAPACITY> {
+    pub data: heapless::String<N>,
 }
 
-impl Message for Bool {
-    fn serialize(&self, buf: &mut [u8]) -> u16 {
-        buf[0] = self.data as u8;
-        1
+impl<const N: usize> Md5Sum for String<N> {
+    const MD5: &'static str = md5sum!("string data");
+}
+
+impl<const N: usize> Message for String<N> {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        self.data.encode_field(encoder);
     }
 
     fn deserialize(&mut self, buf: &[u8]) -> u16 {
-        self.data = buf[0] != 0;
-        1
+        self.data.decode_field(buf) as u16
     }
 
     fn name() -> &'static str {
-        "std_msgs/Bool"
+        "std_msgs/String"
     }
 
     fn md5() -> &'static str {
-        "8b94c1b53db61fb6aed406028ad6332a"
+        Self::MD5
     }
 }
 
-#[derive(Default)]
-pub struct Time {
-    data: ros::Time,
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct Empty;
+
+impl Md5Sum for Empty {
+    const MD5: &'static str = md5sum!("");
+}
+
+impl Message for Empty {
+    fn encode(&self, _encoder: &mut dyn Encoder) {}
+
+    fn deserialize(&mut self, _buf: &[u8]) -> u16 {
+        0
+    }
+
+    fn name() -> &'static str {
+        "std_msgs/Empty"
+    }
+
+    fn md5() -> &'static str {
+        Self::MD5
+    }
 }
 
-impl Message for Time {
-    fn serialize(&self, buf: &mut [u8]) -> u16 {
-        buf[0] = (self.data.sec & 0xFF) as u8;
-        buf[1] = ((self.data.sec >> 8) & 0xFF) as u8;
-        buf[2] = ((self.data.sec >> 16) & 0xFF) as u8;
-        buf[3] = ((self.data.sec >> 24) & 0xFF) as u8;
+/// Standard metadata for stamped data
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Header {
+    pub seq: u32,
+    pub stamp: ros::Time,
+    pub frame_id: heapless::String<FRAME_ID_CAPACITY>,
+}
 
-        buf[4] = (self.data.nsec & 0xFF) as u8;
-        buf[5] = ((self.data.nsec >> 8) & 0xFF) as u8;
-        buf[6] = ((self.data.nsec >> 16) & 0xFF) as u8;
-        buf[7] = ((self.data.nsec >> 24) & 0xFF) as u8;
+impl Md5Sum for Header {
+    const MD5: &'static str = md5sum!("uint32 seq\ntime stamp\nstring frame_id");
+}
 
-        8
+impl Message for Header {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        self.seq.encode_field(encoder);
+        self.stamp.encode_field(encoder);
+        self.frame_id.encode_field(encoder);
     }
 
     fn deserialize(&mut self, buf: &[u8]) -> u16 {
-        let sec = (buf[0] as u32)
-                  | ((buf[1] as u32) << 8)
-                  | ((buf[2] as u32) << 16)
-                  | ((buf[3] as u32) << 24);
-        let nsec = (buf[4] as u32)
-                   | ((buf[5] as u32) << 8)
-                   | ((buf[6] as u32) << 16)
-                   | ((buf[7] as u32) << 24);
+        let mut offset = 0;
+        offset += self.seq.decode_field(&buf[offset..]);
+        offset += self.stamp.decode_field(&buf[offset..]);
+        offset += self.frame_id.decode_field(&buf[offset..]);
+        offset as u16
+    }
 
-        self.data.sec = sec;
-        self.data.nsec = nsec;
+    fn name() -> &'static str {
+        "std_msgs/Header"
+    }
 
-        8
+    fn md5() -> &'static str {
+        Self::MD5
     }
+}
 
-    fn name() -> &'static str
-    where Self: Sized {
-        "std_msgs/Time"
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct ColorRGBA {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Md5Sum for ColorRGBA {
+    const MD5: &'static str = md5sum!("float32 r\nfloat32 g\nfloat32 b\nfloat32 a");
+}
+
+impl Message for ColorRGBA {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        self.r.encode_field(encoder);
+        self.g.encode_field(encoder);
+        self.b.encode_field(encoder);
+        self.a.encode_field(encoder);
+    }
+
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        let mut offset = 0;
+        offset += self.r.decode_field(&buf[offset..]);
+        offset += self.g.decode_field(&buf[offset..]);
+        offset += self.b.decode_field(&buf[offset..]);
+        offset += self.a.decode_field(&buf[offset..]);
+        offset as u16
     }
 
-    fn md5() -> &'static str
-    where Self: Sized {
-        "cd7166c74c552c311fbcc2fe5a7bc289"
+    fn name() -> &'static str {
+        "std_msgs/ColorRGBA"
+    }
+
+    fn md5() -> &'static str {
+        Self::MD5
     }
 }
+
+/// One dimension of a `MultiArrayLayout`
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct MultiArrayDimension {
+    pub label: heapless::String<DIMENSION_LABEL_CAPACITY>,
+    pub size: u32,
+    pub stride: u32,
+}
+
+impl Md5Sum for MultiArrayDimension {
+    const MD5: &'static str = md5sum!("string label\nuint32 size\nuint32 stride");
+}
+
+impl Message for MultiArrayDimension {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        self.label.encode_field(encoder);
+        self.size.encode_field(encoder);
+        self.stride.encode_field(encoder);
+    }
+
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        let mut offset = 0;
+        offset += self.label.decode_field(&buf[offset..]);
+        offset += self.size.decode_field(&buf[offset..]);
+        offset += self.stride.decode_field(&buf[offset..]);
+        offset as u16
+    }
+
+    fn name() -> &'static str {
+        "std_msgs/MultiArrayDimension"
+    }
+
+    fn md5() -> &'static str {
+        Self::MD5
+    }
+}
+
+/// How the flat `data` of a `*MultiArray` maps onto its dimensions
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct MultiArrayLayout {
+    pub dim: Vec<MultiArrayDimension, MULTI_ARRAY_DIMENSIONS>,
+    pub data_offset: u32,
+}
+
+impl Md5Sum for MultiArrayLayout {
+    const MD5: &'static str = md5sum!(
+        "MultiArrayDimension[] dim\nuint32 data_offset",
+        "MultiArrayDimension" => MultiArrayDimension,
+    );
+}
+
+impl Message for MultiArrayLayout {
+    fn encode(&self, encoder: &mut dyn Encoder) {
+        self.dim.encode_field(encoder);
+        self.data_offset.encode_field(encoder);
+    }
+
+    fn deserialize(&mut self, buf: &[u8]) -> u16 {
+        let mut offset = 0;
+        offset += self.dim.decode_field(&buf[offset..]);
+        offset += self.data_offset.decode_field(&buf[offset..]);
+        offset as u16
+    }
+
+    fn name() -> &'static str {
+        "std_msgs/MultiArrayLayout"
+    }
+
+    fn md5() -> &'static str {
+        Self::MD5
+    }
+}
+
+/// Multi-dimensional arrays, holding up to `N` elements
+macro_rules! multi_array_message {
+    ($($name:ident($ty:ty) = $definition:literal;)*) => {
+        $(
+            #[derive(Default, Clone, Debug, PartialEq)]
+            pub struct $name<const N: usize = MULTI_ARRAY_CAPACITY> {
+                pub layout: MultiArrayLayout,
+                pub data: Vec<$ty, N>,
+            }
+
+            impl<const N: usize> Md5Sum for $name<N> {
+                const MD5: &'static str = md5sum!($definition, "MultiArrayLayout" => MultiArrayLayout);
+            }
+
+            impl<const N: usize> Message for $name<N> {
+                fn encode(&self, encoder: &mut dyn Encoder) {
+                    self.layout.encode_field(encoder);
+                    self.data.encode_field(encoder);
+                }
+
+                fn deserialize(&mut self, buf: &[u8]) -> u16 {
+                    let mut offset = 0;
+                    offset += self.layout.decode_field(&buf[offset..]);
+                    offset += self.data.decode_field(&buf[offset..]);
+                    offset as u16
+                }
+
+                fn name() -> &'static str {
+                    concat!("std_msgs/", stringify!($name))
+                }
+
+                fn md5() -> &'static str {
+                    Self::MD5
+                }
+            }
+        )*
+    };
+}
+
+multi_array_message! {
+    ByteMultiArray(i8) = "MultiArrayLayout layout\nbyte[] data";
+    Int8MultiArray(i8) = "MultiArrayLayout layout\nint8[] data";
+    UInt8MultiArray(u8) = "MultiArrayLayout layout\nuint8[] data";
+    Int16MultiArray(i16) = "MultiArrayLayout layout\nint16[] data";
+    UInt16MultiArray(u16) = "MultiArrayLayout layout\nuint16[] data";
+    Int32MultiArray(i32) = "MultiArrayLayout layout\nint32[] data";
+    UInt32MultiArray(u32) = "MultiArrayLayout layout\nuint32[] data";
+    Int64MultiArray(i64) = "MultiArrayLayout layout\nint64[] data";
+    UInt64MultiArray(u64) = "MultiArrayLayout layout\nuint64[] data";
+    Float32MultiArray(f32) = "MultiArrayLayout layout\nfloat32[] data";
+    Float64MultiArray(f64) = "MultiArrayLayout layout\nfloat64[] data";
+}
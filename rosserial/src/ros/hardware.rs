@@ -2,4 +2,30 @@ pub trait HardwareInterface {
     fn read(&mut self) -> Option<u8>;
     fn write(&mut self, data: u8);
     fn time(&self) -> u32;
+
+    /// Read as many bytes as are available, up to `buf.len()`, returning the number read.
+    /// DMA backed transports can override this to copy out whole blocks.
+    fn read_into(&mut self, buf: &mut [u8]) -> usize {
+        let mut n = 0;
+
+        for b in buf.iter_mut() {
+            match self.read() {
+                Some(data) => *b = data,
+                None => break,
+            }
+            n += 1;
+        }
+
+        n
+    }
+
+    /// Write a block of bytes. DMA backed transports can override this to queue the whole block at once
+    fn write_all(&mut self, data: &[u8]) {
+        for b in data {
+            self.write(*b);
+        }
+    }
+
+    /// Called after the last byte of each frame has been written, so buffered transports can send it
+    fn flush(&mut self) {}
 }
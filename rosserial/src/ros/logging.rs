@@ -0,0 +1,208 @@
+use core::fmt;
+
+/// Fixed size buffer that can be formatted into with `core::fmt::Write`.
+/// Output that does not fit is truncated.
+pub struct LogBuffer<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> LogBuffer<'b> {
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        LogBuffer {
+            buf,
+            len: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Truncation only happens on a char boundary, so the contents are always valid UTF-8
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl fmt::Write for LogBuffer<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let space = self.buf.len() - self.len;
+
+        let mut n = s.len().min(space);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+
+        if n < s.len() { Err(fmt::Error) } else { Ok(()) }
+    }
+}
+
+#[cfg(feature = "log")]
+pub use self::logger::*;
+
+#[cfg(feature = "log")]
+mod logger {
+    use core::cell::UnsafeCell;
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use super::LogBuffer;
+    use crate::msgs::rosserial_msgs;
+    use crate::ros::{HardwareInterface, NodeHandle};
+
+    const LINE_SIZE: usize = 128;
+    const QUEUE_SIZE: usize = 4;
+
+    struct Line {
+        level: u8,
+        len: usize,
+        data: [u8; LINE_SIZE],
+    }
+
+    struct Queue {
+        lines: [Line; QUEUE_SIZE],
+        head: usize,
+        count: usize,
+    }
+
+    /// Adapter for the `log` crate facade.
+    ///
+    /// Records are queued in a static buffer and sent to `/rosout` by calling `flush` from the spin loop.
+    /// Records logged while the queue is full, or while it is being accessed from another context, are dropped.
+    ///
+    /// On targets without atomic compare and swap, e.g. thumbv6m, the queue is claimed in a critical section,
+    /// so a `critical-section` implementation must be linked in, e.g. from `cortex-m`.
+    ///
+    /// ```ignore
+    /// static LOGGER: Logger = Logger::new(log::LevelFilter::Info);
+    ///
+    /// log::set_logger(&LOGGER).ok();
+    /// log::set_max_level(log::LevelFilter::Info);
+    ///
+    /// loop {
+    ///     nodehandle.spin_once(&mut hardware);
+    ///     LOGGER.flush(&nodehandle, &mut hardware);
+    /// }
+    /// ```
+    pub struct Logger {
+        level: log::LevelFilter,
+        busy: AtomicBool,
+        queue: UnsafeCell<Queue>,
+    }
+
+    // Access to the queue is guarded by `busy`
+    unsafe impl Sync for Logger {}
+
+    impl Logger {
+        pub const fn new(level: log::LevelFilter) -> Self {
+            const EMPTY: Line = Line { level: 0, len: 0, data: [0; LINE_SIZE] };
+
+            Logger {
+                level,
+                busy: AtomicBool::new(false),
+                queue: UnsafeCell::new(Queue {
+                    lines: [EMPTY; QUEUE_SIZE],
+                    head: 0,
+                    count: 0,
+                }),
+            }
+        }
+
+        /// Send queued records
+        pub fn flush<const MAX_PUBS: usize, const MAX_SUBS: usize, const IN_BUF: usize, const OUT_BUF: usize>(
+            &self,
+            nodehandle: &NodeHandle<'_, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>,
+            hardware: &mut dyn HardwareInterface,
+        ) {
+            let mut data = [0u8; LINE_SIZE];
+
+            while let Some((level, len)) = self.with_queue(|queue| Logger::pop(queue, &mut data)).flatten() {
+                let msg = core::str::from_utf8(&data[..len]).unwrap_or("");
+                nodehandle.log(level, msg, hardware);
+            }
+        }
+
+        fn with_queue<R, F: FnOnce(&mut Queue) -> R>(&self, f: F) -> Option<R> {
+            if !self.try_claim() {
+                return None;
+            }
+
+            // Safe as `busy` was acquired above
+            let result = f(unsafe { &mut *self.queue.get() });
+            self.busy.store(false, Ordering::Release);
+
+            Some(result)
+        }
+
+        /// Set `busy`, returning false if it was already set
+        #[cfg(target_has_atomic = "8")]
+        fn try_claim(&self) -> bool {
+            self.busy.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+        }
+
+        /// Set `busy`, returning false if it was already set. Without compare and swap the flag is
+        /// checked and set in a critical section instead
+        #[cfg(not(target_has_atomic = "8"))]
+        fn try_claim(&self) -> bool {
+            critical_section::with(|_| {
+                if self.busy.load(Ordering::Acquire) {
+                    return false;
+                }
+
+                self.busy.store(true, Ordering::Relaxed);
+                true
+            })
+        }
+
+        fn pop(queue: &mut Queue, data: &mut [u8; LINE_SIZE]) -> Option<(u8, usize)> {
+            if queue.count == 0 {
+                return None;
+            }
+
+            let line = &queue.lines[queue.head];
+            data[..line.len].copy_from_slice(&line.data[..line.len]);
+            let popped = (line.level, line.len);
+
+            queue.head = (queue.head + 1) % QUEUE_SIZE;
+            queue.count -= 1;
+
+            Some(popped)
+        }
+    }
+
+    impl log::Log for Logger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= self.level
+        }
+
+        fn log(&self, record: &log::Record) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+
+            let level = match record.level() {
+                log::Level::Error => rosserial_msgs::LOG_ERROR,
+                log::Level::Warn => rosserial_msgs::LOG_WARN,
+                log::Level::Info => rosserial_msgs::LOG_INFO,
+                log::Level::Debug | log::Level::Trace => rosserial_msgs::LOG_ROSDEBUG,
+            };
+
+            self.with_queue(|queue| {
+                if queue.count == QUEUE_SIZE {
+                    return;
+                }
+
+                let line = &mut queue.lines[(queue.head + queue.count) % QUEUE_SIZE];
+
+                let mut buf = LogBuffer::new(&mut line.data);
+                write!(buf, "{}", record.args()).ok();
+
+                line.len = buf.as_str().len();
+                line.level = level;
+                queue.count += 1;
+            });
+        }
+
+        fn flush(&self) {}
+    }
+}
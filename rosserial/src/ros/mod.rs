@@ -2,9 +2,15 @@ mod nodehandle;
 mod hardware;
 mod topics;
 mod time;
+mod logging;
+#[cfg(feature = "async")]
+mod nodehandle_async;
 
 // Public re-exports
 pub use nodehandle::*;
 pub use hardware::*;
 pub use topics::*;
 pub use time::*;
+pub use logging::*;
+#[cfg(feature = "async")]
+pub use nodehandle_async::*;
@@ -1,13 +1,59 @@
-use super::{HardwareInterface, Publisher, MessageHandler, TopicBase};
-use crate::msgs::{Message, rosserial_msgs, std_msgs};
+use super::{HardwareInterface, Publisher, MessageHandler, TopicBase, ServiceServer, ServiceClient, Time, LogBuffer};
+use crate::msgs::{Encoder, Message, Service, rosserial_msgs, std_msgs};
+
+use core::cell::Cell;
+use core::fmt::{self, Write};
 
 // use alloc::boxed::Box;
 
-pub type PublisherHandle = usize;
+/// Handle to an advertised topic, only accepting the message type it was advertised with
+pub struct PublisherHandle<Msg> {
+    index: usize,
+    phantom_msg: core::marker::PhantomData<Msg>,
+}
+
+impl<Msg> Clone for PublisherHandle<Msg> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Msg> Copy for PublisherHandle<Msg> {}
 
 #[derive(Debug)]
 pub enum NodeHandleError {
     MaxPublishersReached,
+    MaxSubscribersReached,
+    NotConfigured,
+    InvalidHandle,
+    MessageTooLarge,
+    Timeout,
+    /// The parameter does not exist, or its type or length does not match the request
+    ParameterMismatch,
+}
+
+/// Counters for frames dropped by the receive state machine
+#[derive(Default, Clone, Copy, Debug)]
+pub struct LinkStats {
+    /// Frames whose message length checksum did not match
+    pub length_checksum_errors: u32,
+    /// Frames whose topic + payload checksum did not match
+    pub message_checksum_errors: u32,
+    /// Frames too large for the receive buffer or the subscriber's buffer size, which were skipped
+    pub overflow_errors: u32,
+    /// Outgoing messages too large for the output buffer, which were not sent
+    pub tx_overflow_errors: u32,
+}
+
+/// State of the link with the host
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionState {
+    /// Waiting for the host to request topics
+    Disconnected,
+    /// Topics sent, waiting for the first time sync reply
+    Negotiating,
+    /// Topics negotiated and time sync replies arriving within the sync timeout
+    Connected,
 }
 
 enum State {
@@ -19,219 +65,790 @@ enum State {
     TopicIdLsb,
     TopicIdMsb,
     Message,
-    MessageChecksum
+    MessageChecksum,
+    Skip,
 }
 
 // const PROTOCOL_VER1: u8 = 0xFF;
-const PROTOCOL_VER2: u8 = 0xFE;
-
-const MESSAGE_BUFFER_SIZE: usize = 1024;
-const MAX_PUB_SUBS: usize = 256;
-
-
-pub struct NodeHandle<'a> {
+pub(crate) const PROTOCOL_VER2: u8 = 0xFE;
+
+// Topic ids for publishers and subscribers start here, below are reserved for rosserial_msgs::TOPICINFO_ID_*
+const TOPIC_ID_OFFSET: usize = 100;
+// Sync, protocol version, length (2), length checksum, topic id (2) and message checksum
+const FRAME_OVERHEAD: usize = 8;
+// TopicInfo with an empty topic name and type: id, three string lengths, the md5sum and the buffer size.
+// Only a floor, topics whose names do not fit are refused when they are added
+const MIN_TOPIC_INFO_SIZE: usize = 2 + 3 * 4 + 32 + 4;
+const LOG_BUFFER_SIZE: usize = 128;
+
+// Same as rosserial_client, which allows 2.2x its 5 second sync period
+const DEFAULT_SYNC_TIMEOUT: u32 = 11_000;
+
+
+/// Node handle, sized by the maximum number of publishers and subscribers, the
+/// receive buffer size and the largest transmitted frame, like rosserial_arduino's `NodeHandle_`.
+///
+/// The defaults match rosserial_arduino. Smaller boards can shrink them:
+///
+/// ```ignore
+/// let mut nodehandle: NodeHandle<'_, 4, 4, 128, 128> = NodeHandle::default();
+/// ```
+pub struct NodeHandle<'a, const MAX_PUBS: usize = 25, const MAX_SUBS: usize = 25, const IN_BUF: usize = 512, const OUT_BUF: usize = 512> {
     state: State,
-    message_in: [u8; MESSAGE_BUFFER_SIZE],
+    message_in: [u8; IN_BUF],
     index: usize,
     bytes: u16,
     topic: u16,
-    checksum: u16,
-    configured: bool,
-
-    publishers: [Option<Publisher>; MAX_PUB_SUBS],
-    subscribers: [Option<&'a mut dyn MessageHandler>; MAX_PUB_SUBS],
-    subscriber_info: [Option<rosserial_msgs::TopicInfo>; MAX_PUB_SUBS],
+    checksum: u8,
+    connection: ConnectionState,
+    state_callback: Option<&'a mut dyn FnMut(ConnectionState)>,
+    stats: LinkStats,
+    // Counted from `&self` methods, so kept apart from `stats`
+    tx_overflow_errors: Cell<u32>,
+    // Topic id of the last complete frame, cleared when the next frame starts
+    received_topic: Option<u16>,
+
+    // Time synchronisation
+    sync_timeout: u32,
+    rt_time: u32,
+    time_offset: i64,
+    last_sync_receive_time: u32,
+
+    publishers: [Option<Publisher>; MAX_PUBS],
+    subscribers: [Option<&'a mut dyn MessageHandler>; MAX_SUBS],
+    // Endpoint type and topic info announced for each subscriber
+    subscriber_info: [Option<(u16, rosserial_msgs::TopicInfo<'static>)>; MAX_SUBS],
 }
 
-impl<'a> Default for NodeHandle<'a> {
+impl<'a, const MAX_PUBS: usize, const MAX_SUBS: usize, const IN_BUF: usize, const OUT_BUF: usize> Default for NodeHandle<'a, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF> {
     fn default() -> Self {
+        let () = Self::SIZE_CHECK;
+
         NodeHandle {
             state: State::Sync,
-            message_in: [0; 1024],
+            message_in: [0; IN_BUF],
             index: 0,
             bytes: 0,
             topic: 0,
             checksum: 0,
-            configured: false,
-
-            publishers: [None; MAX_PUB_SUBS],
-            subscribers: [None; MAX_PUB_SUBS],
-            subscriber_info: [None; MAX_PUB_SUBS],
+            connection: ConnectionState::Disconnected,
+            state_callback: None,
+            stats: LinkStats::default(),
+            tx_overflow_errors: Cell::new(0),
+            received_topic: None,
+
+            sync_timeout: DEFAULT_SYNC_TIMEOUT,
+            rt_time: 0,
+            time_offset: 0,
+            last_sync_receive_time: 0,
+
+            publishers: [None; MAX_PUBS],
+            subscribers: [Self::NO_SUBSCRIBER; MAX_SUBS],
+            subscriber_info: [None; MAX_SUBS],
         }
     }
 }
 
-impl<'a> NodeHandle<'a> {
-    pub fn advertise<Msg: Message>(&mut self, topic: &'static str) -> Result<PublisherHandle, NodeHandleError> {
+impl<'a, const MAX_PUBS: usize, const MAX_SUBS: usize, const IN_BUF: usize, const OUT_BUF: usize> NodeHandle<'a, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF> {
+    const NO_SUBSCRIBER: Option<&'a mut dyn MessageHandler> = None;
+
+    // Evaluated when a node handle is created, so invalid sizes fail to compile
+    const SIZE_CHECK: () = {
+        assert!(TOPIC_ID_OFFSET + MAX_PUBS <= u16::MAX as usize, "too many publishers for 16 bit topic ids");
+        assert!(TOPIC_ID_OFFSET + MAX_SUBS <= u16::MAX as usize, "too many subscribers for 16 bit topic ids");
+        assert!(IN_BUF <= u16::MAX as usize, "input buffer larger than the maximum message length");
+        assert!(IN_BUF >= 8, "input buffer too small for the time sync reply");
+        assert!(OUT_BUF >= FRAME_OVERHEAD + MIN_TOPIC_INFO_SIZE, "output buffer too small to announce topics");
+        assert!(OUT_BUF - FRAME_OVERHEAD <= u16::MAX as usize, "output buffer larger than the maximum frame size");
+    };
+
+    pub fn advertise<Msg: Message>(&mut self, topic: &'static str) -> Result<PublisherHandle<Msg>, NodeHandleError> {
+        let index = self.add_publisher(|id| Publisher::new(topic, id, Msg::name(), Msg::md5()))?;
+
+        Ok(PublisherHandle {
+            index,
+            phantom_msg: Default::default(),
+        })
+    }
+
+    pub fn register_subscriber<Sub: MessageHandler + TopicBase, Msg: Message>(&mut self, sub: &'a mut Sub) -> Result<(), NodeHandleError> {
+        self.add_subscriber(sub)
+    }
+
+    /// Register a service server. Requests are received on a subscriber and responses sent on a paired publisher
+    pub fn register_service_server<Srv, F>(&mut self, srv: &'a mut ServiceServer<Srv, F>) -> Result<(), NodeHandleError>
+    where
+        Srv: Service + 'a,
+        F: FnMut(&Srv::Request, &mut Srv::Response) + 'a,
+    {
+        if !self.subscriber_info.iter().any(|item| item.is_none()) {
+            return Err(NodeHandleError::MaxSubscribersReached);
+        }
+
+        let handle = self.add_publisher(|id| srv.response_info(id))?;
+        srv.response_id = self.publishers[handle].map(|p| p.id);
+
+        self.add_subscriber(srv)
+    }
+
+    fn add_publisher<F: FnOnce(u16) -> Publisher>(&mut self, publisher: F) -> Result<usize, NodeHandleError> {
         // Find the next available slot
-        let slot = self.publishers.iter_mut().filter(|item| item.is_none()).enumerate().next();
+        let slot = self.publishers.iter_mut().enumerate().find(|(_, item)| item.is_none());
 
         if let Some((i, slot)) = slot {
-            let handle = i;
-            *slot = Some(Publisher::new(topic, (i + 100) as u16, Msg::name(), Msg::md5()));
-            Ok(handle)
+            let publisher = publisher((i + TOPIC_ID_OFFSET) as u16);
+            Self::check_topic_info(&publisher.into())?;
+
+            *slot = Some(publisher);
+            Ok(i)
         }
         else {
             Err(NodeHandleError::MaxPublishersReached)
         }
     }
 
-    pub fn register_subscriber<Sub: MessageHandler + TopicBase, Msg: Message>(&mut self, sub: &'a mut Sub) {
-        let slot = self.subscribers.iter_mut().filter(|item| item.is_none()).enumerate().next();
+    /// Create a client for a service provided by the host
+    pub fn service_client<Srv: Service>(&mut self, topic: &'static str) -> Result<ServiceClient<Srv>, NodeHandleError> {
+        if !self.subscriber_info.iter().any(|item| item.is_none()) {
+            return Err(NodeHandleError::MaxSubscribersReached);
+        }
+
+        let handle = self.add_publisher(|id| {
+            let mut p = Publisher::new(topic, id, Srv::name(), Srv::Request::md5());
+            p.endpoint = rosserial_msgs::TOPICINFO_ID_SERVICE_CLIENT + rosserial_msgs::TOPICINFO_ID_PUBLISHER;
+            p
+        })?;
+        let request_id = (handle + TOPIC_ID_OFFSET) as u16;
+
+        let endpoint = rosserial_msgs::TOPICINFO_ID_SERVICE_CLIENT + rosserial_msgs::TOPICINFO_ID_SUBSCRIBER;
+        let i = self.add_subscriber_info(endpoint, topic, Srv::name(), Srv::Response::md5())?;
+
+        Ok(ServiceClient::new(topic, request_id, (i + TOPIC_ID_OFFSET) as u16))
+    }
+
+    /// Call a service on the host, spinning until the response arrives or `timeout` (ms) elapses
+    pub fn call<Srv: Service>(&mut self, client: &ServiceClient<Srv>, request: &Srv::Request, hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<Srv::Response, NodeHandleError> {
+        self.send_request(client, request, hardware)?;
+
+        let start = hardware.time();
+
+        loop {
+            self.spin_once(hardware);
+
+            if let Some(response) = self.poll_response(client) {
+                return Ok(response);
+            }
+
+            if !self.connected() {
+                return Err(NodeHandleError::NotConfigured);
+            }
+
+            if hardware.time().wrapping_sub(start) >= timeout {
+                return Err(NodeHandleError::Timeout);
+            }
+        }
+    }
+
+    /// Send a service request without waiting for the response. See `poll_response`
+    pub fn send_request<Srv: Service>(&mut self, client: &ServiceClient<Srv>, request: &Srv::Request, hardware: &mut dyn HardwareInterface) -> Result<(), NodeHandleError> {
+        if !self.connected() {
+            return Err(NodeHandleError::NotConfigured);
+        }
+
+        self.received_topic = None;
+        self.send_message(client.request_id, request, hardware)?;
+
+        Ok(())
+    }
+
+    /// Check if the response to the last request has arrived.
+    /// Must be called after every `spin_once`, the response is discarded once the next frame starts.
+    pub fn poll_response<Srv: Service>(&mut self, client: &ServiceClient<Srv>) -> Option<Srv::Response> {
+        if self.received_topic != Some(client.response_id) {
+            return None;
+        }
+
+        self.received_topic = None;
+
+        let mut response = Srv::Response::default();
+        response.deserialize(&self.message_in[..self.index]);
+
+        Some(response)
+    }
+
+    pub fn get_param_int(&mut self, name: &str, hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<i32, NodeHandleError> {
+        let mut value = [0];
+        self.get_param_ints(name, &mut value, hardware, timeout)?;
+        Ok(value[0])
+    }
+
+    pub fn get_param_float(&mut self, name: &str, hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<f32, NodeHandleError> {
+        let mut value = [0.0];
+        self.get_param_floats(name, &mut value, hardware, timeout)?;
+        Ok(value[0])
+    }
+
+    /// Read a string parameter into `buf`, returning the part of `buf` that was used
+    pub fn get_param_string<'b>(&mut self, name: &str, buf: &'b mut [u8], hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<&'b str, NodeHandleError> {
+        let mut value = [""];
+        self.get_param_strings(name, buf, &mut value, hardware, timeout)?;
+        Ok(value[0])
+    }
+
+    /// Read an integer array parameter. The parameter must have exactly `values.len()` elements
+    pub fn get_param_ints(&mut self, name: &str, values: &mut [i32], hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<(), NodeHandleError> {
+        let response = self.request_param(name, hardware, timeout)?;
+        read_param_ints(&response, values)
+    }
+
+    /// Read a float array parameter. The parameter must have exactly `values.len()` elements
+    pub fn get_param_floats(&mut self, name: &str, values: &mut [f32], hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<(), NodeHandleError> {
+        let response = self.request_param(name, hardware, timeout)?;
+        read_param_floats(&response, values)
+    }
+
+    /// Read a string array parameter. The parameter must have exactly `values.len()` elements.
+    /// The strings are copied into `buf` and `values` is set to point at each of them.
+    pub fn get_param_strings<'b>(&mut self, name: &str, buf: &'b mut [u8], values: &mut [&'b str], hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<(), NodeHandleError> {
+        let response = self.request_param(name, hardware, timeout)?;
+        read_param_strings(&response, buf, values)
+    }
+
+    fn request_param(&mut self, name: &str, hardware: &mut dyn HardwareInterface, timeout: u32) -> Result<rosserial_msgs::RequestParamResponse<'_>, NodeHandleError> {
+        if !self.connected() {
+            return Err(NodeHandleError::NotConfigured);
+        }
+
+        let request = rosserial_msgs::RequestParamRequest { name };
+        self.received_topic = None;
+        self.send_message(rosserial_msgs::TOPICINFO_ID_PARAMETER_REQUEST, &request, hardware)?;
+
+        let start = hardware.time();
+
+        loop {
+            self.spin_once(hardware);
+
+            if self.received_topic == Some(rosserial_msgs::TOPICINFO_ID_PARAMETER_REQUEST) {
+                self.received_topic = None;
+                return rosserial_msgs::RequestParamResponse::parse(&self.message_in[..self.index])
+                    .ok_or(NodeHandleError::ParameterMismatch);
+            }
+
+            if !self.connected() {
+                return Err(NodeHandleError::NotConfigured);
+            }
+
+            if hardware.time().wrapping_sub(start) >= timeout {
+                return Err(NodeHandleError::Timeout);
+            }
+        }
+    }
+
+    fn add_subscriber<Sub: MessageHandler + TopicBase>(&mut self, sub: &'a mut Sub) -> Result<(), NodeHandleError> {
+        let i = self.add_subscriber_info(sub.endpoint(), sub.topic(), sub.message_type(), sub.md5sum())?;
+        self.subscribers[i] = Some(sub);
+
+        Ok(())
+    }
+
+    fn add_subscriber_info(&mut self, endpoint: u16, topic: &'static str, message_type: &'static str, md5: &'static str) -> Result<usize, NodeHandleError> {
+        let slot = self.subscriber_info.iter_mut().enumerate().find(|(_, item)| item.is_none());
+
         if let Some((i, slot)) = slot {
             // Info for this topic
-            let mut ti = rosserial_msgs::TopicInfo::default();
-            ti.id = (i as u16) + 100;
-            ti.name = sub.topic();
-            ti.message_type = sub.message_type();
-            ti.md5 = sub.md5sum();
-            ti.buffer_size = 256;
+            let ti = rosserial_msgs::TopicInfo {
+                id: (i + TOPIC_ID_OFFSET) as u16,
+                name: topic,
+                message_type,
+                md5,
+                buffer_size: IN_BUF as u32,
+            };
+            Self::check_topic_info(&ti)?;
+
+            *slot = Some((endpoint, ti));
+            Ok(i)
+        }
+        else {
+            Err(NodeHandleError::MaxSubscribersReached)
+        }
+    }
 
-            self.subscriber_info[i] = Some(ti);
+    /// Topics are announced on every negotiation, so refuse any whose info does not fit the output buffer
+    fn check_topic_info(ti: &rosserial_msgs::TopicInfo) -> Result<(), NodeHandleError> {
+        if ti.serialized_len() > OUT_BUF - FRAME_OVERHEAD {
+            Err(NodeHandleError::MessageTooLarge)
+        }
+        else {
+            Ok(())
+        }
+    }
 
-            *slot = Some(sub);
+    /// Publish a message on an advertised topic. Fails if the host has not negotiated topics yet
+    pub fn publish<Msg: Message>(&self, handle: PublisherHandle<Msg>, msg: &Msg, hardware: &mut dyn HardwareInterface) -> Result<(), NodeHandleError> {
+        let p = self.publishers.get(handle.index)
+            .and_then(|p| p.as_ref())
+            .ok_or(NodeHandleError::InvalidHandle)?;
 
+        if !self.connected() {
+            return Err(NodeHandleError::NotConfigured);
         }
+
+        self.send_message(p.id, msg, hardware)
+    }
+
+    /// Send a log message to `/rosout`. `level` is one of the `rosserial_msgs::LOG_*` constants.
+    /// Messages too large for the output buffer are dropped and counted in `stats().tx_overflow_errors`
+    pub fn log(&self, level: u8, msg: &str, hardware: &mut dyn HardwareInterface) {
+        let log = rosserial_msgs::Log { level, msg };
+        self.send_message(rosserial_msgs::TOPICINFO_ID_LOG, &log, hardware).ok();
+    }
+
+    /// Format a log message into a fixed size buffer and send it. Long messages are truncated
+    ///
+    /// ```ignore
+    /// nodehandle.log_fmt(rosserial_msgs::LOG_INFO, format_args!("speed: {}", speed), &mut hardware);
+    /// ```
+    pub fn log_fmt(&self, level: u8, args: fmt::Arguments, hardware: &mut dyn HardwareInterface) {
+        let mut data = [0u8; LOG_BUFFER_SIZE];
+        let mut buf = LogBuffer::new(&mut data);
+        buf.write_fmt(args).ok();
+
+        self.log(level, buf.as_str(), hardware);
+    }
+
+    pub fn logdebug(&self, msg: &str, hardware: &mut dyn HardwareInterface) {
+        self.log(rosserial_msgs::LOG_ROSDEBUG, msg, hardware);
+    }
+
+    pub fn loginfo(&self, msg: &str, hardware: &mut dyn HardwareInterface) {
+        self.log(rosserial_msgs::LOG_INFO, msg, hardware);
+    }
+
+    pub fn logwarn(&self, msg: &str, hardware: &mut dyn HardwareInterface) {
+        self.log(rosserial_msgs::LOG_WARN, msg, hardware);
+    }
+
+    pub fn logerror(&self, msg: &str, hardware: &mut dyn HardwareInterface) {
+        self.log(rosserial_msgs::LOG_ERROR, msg, hardware);
     }
 
-    pub fn publish(&self, handle: usize, msg: &dyn Message, hardware: &mut dyn HardwareInterface) {
-        if let Some(ref p) = self.publishers[handle] {
-            self.send_message(p.id, msg, hardware);
+    pub fn logfatal(&self, msg: &str, hardware: &mut dyn HardwareInterface) {
+        self.log(rosserial_msgs::LOG_FATAL, msg, hardware);
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connection == ConnectionState::Connected
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        self.connection
+    }
+
+    /// Set a callback run whenever the connection state changes, e.g. to stop motors when the host goes away
+    pub fn set_state_callback(&mut self, callback: &'a mut dyn FnMut(ConnectionState)) {
+        self.state_callback = Some(callback);
+    }
+
+    /// Time (ms) allowed between time sync replies before the link is considered lost.
+    /// Time sync is requested automatically at a quarter of this period while connected.
+    pub fn set_sync_timeout(&mut self, timeout: u32) {
+        self.sync_timeout = timeout;
+    }
+
+    /// Error counters, useful as a measure of link quality
+    pub fn stats(&self) -> LinkStats {
+        LinkStats {
+            tx_overflow_errors: self.tx_overflow_errors.get(),
+            ..self.stats
         }
     }
 
     pub fn spin_once(&mut self, hardware: &mut dyn HardwareInterface) {
-        // let current_time = hardware.time();
+        self.check_connection(hardware);
 
-        let data = hardware.read();
+        if let State::Message = self.state {
+            // Read the payload straight into the receive buffer
+            let end = self.index + self.bytes as usize;
+            let n = hardware.read_into(&mut self.message_in[self.index..end]);
 
-        if let Some(data) = data {
-            match self.state {
-                State::Sync => {
-                    if data == 0xFF {
-                        self.state = State::ProtocolVersion;
-                    }
-                },
-                State::ProtocolVersion => {
-                    self.state = if data == PROTOCOL_VER2 {
-                        State::SizeLsb
-                    }
-                    else {
-                        State::Sync
-                    };
-                },
-                State::SizeLsb => {
-                    self.bytes = data as u16;
-                    self.index = 0;
-                    self.checksum = data as u16; // first byte to calculate checksum
-                    self.state = State::SizeMsb;
-                },
-                State::SizeMsb => {
-                    self.bytes |= (data as u16) << 8;
-                    self.state = State::SizeChecksum;
-                },
-                State::SizeChecksum => {
-                    // Message Length Checksum = 255 - ((Message Length High Byte + Message Length Low Byte) % 256 )
-                    // TODO(nnarain): This doesn't work?
-                    // state = if checksum % 256 == 255 {
-                    //     State::TopicIdLsb
-                    // }
-                    // else {
-                    //     State::Sync
-                    // };
-                    self.state = State::TopicIdLsb;
-                },
-                State::TopicIdLsb => {
-                    self.topic = data as u16;
-                    self.checksum = data as u16;
-                    self.state = State::TopicIdMsb;
-                },
-                State::TopicIdMsb => {
-                    self.topic |= (data as u16) << 8;
-                    self.state = if self.bytes == 0 { State::MessageChecksum } else { State::Message };
-                },
-                State::Message => {
-                    self.message_in[self.index] = data;
-                    self.index += 1;
+            for data in &self.message_in[self.index..self.index + n] {
+                self.checksum = self.checksum.wrapping_add(*data);
+            }
+
+            self.index += n;
+            self.bytes -= n as u16;
+
+            if self.bytes == 0 {
+                self.state = State::MessageChecksum;
+            }
+
+            return;
+        }
+
+        // Never read past the end of the current frame, so each call completes at most one frame
+        let mut buf = [0u8; FRAME_OVERHEAD];
+        let wanted = self.bytes_to_frame_boundary().min(buf.len());
+        let n = hardware.read_into(&mut buf[..wanted]);
+
+        for data in &buf[..n] {
+            if self.handle_byte(*data) {
+                self.dispatch(hardware);
+            }
+        }
+    }
+
+    /// Number of bytes that can be read without passing the end of the current frame's header or payload
+    fn bytes_to_frame_boundary(&self) -> usize {
+        match self.state {
+            State::Sync => 1,
+            State::ProtocolVersion => 6,
+            State::SizeLsb => 5,
+            State::SizeMsb => 4,
+            State::SizeChecksum => 3,
+            State::TopicIdLsb => 2,
+            State::TopicIdMsb => 1,
+            State::Message | State::Skip => self.bytes as usize + 1,
+            State::MessageChecksum => 1,
+        }
+    }
+
+    /// Run the receive state machine on one byte, returning true when a valid frame is complete
+    pub(crate) fn handle_byte(&mut self, data: u8) -> bool {
+        match self.state {
+            State::Sync => {
+                if data == 0xFF {
+                    self.state = State::ProtocolVersion;
+                    self.received_topic = None;
+                }
+            },
+            State::ProtocolVersion => {
+                self.state = if data == PROTOCOL_VER2 {
+                    State::SizeLsb
+                }
+                else {
+                    State::Sync
+                };
+            },
+            State::SizeLsb => {
+                self.bytes = data as u16;
+                self.index = 0;
+                self.checksum = data; // first byte to calculate checksum
+                self.state = State::SizeMsb;
+            },
+            State::SizeMsb => {
+                self.bytes |= (data as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(data);
+                self.state = State::SizeChecksum;
+            },
+            State::SizeChecksum => {
+                // Message Length Checksum = 255 - ((Message Length High Byte + Message Length Low Byte) % 256 )
+                self.state = if self.checksum.wrapping_add(data) == 0xFF {
+                    State::TopicIdLsb
+                }
+                else {
+                    self.stats.length_checksum_errors += 1;
+                    State::Sync
+                };
+            },
+            State::TopicIdLsb => {
+                self.topic = data as u16;
+                self.checksum = data;
+                self.state = State::TopicIdMsb;
+            },
+            State::TopicIdMsb => {
+                self.topic |= (data as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(data);
+                self.state = if self.bytes as usize > self.max_message_size(self.topic) {
+                    self.stats.overflow_errors += 1;
+                    State::Skip
+                }
+                else if self.bytes == 0 {
+                    State::MessageChecksum
+                }
+                else {
+                    State::Message
+                };
+            },
+            State::Message => {
+                self.message_in[self.index] = data;
+                self.index += 1;
+                self.bytes -= 1;
+                self.checksum = self.checksum.wrapping_add(data);
+
+                if self.bytes == 0 {
+                    self.state = State::MessageChecksum;
+                }
+            },
+            State::MessageChecksum => {
+                self.state = State::Sync;
+
+                // Message Checksum = 255 - ((Topic ID Low Byte + Topic ID High Byte + data byte values) % 256)
+                if self.checksum.wrapping_add(data) != 0xFF {
+                    self.stats.message_checksum_errors += 1;
+                    return false;
+                }
+
+                self.received_topic = Some(self.topic);
+                return true;
+            },
+            State::Skip => {
+                // Drop the payload of an oversized frame, then its checksum
+                if self.bytes == 0 {
+                    self.state = State::Sync;
+                }
+                else {
                     self.bytes -= 1;
+                }
+            },
+        }
 
-                    if self.bytes == 0 {
-                        self.state = State::MessageChecksum;
-                    }
-                },
-                State::MessageChecksum => {
-                    // TODO: checksum
+        false
+    }
 
-                    if self.topic == rosserial_msgs::TOPICINFO_ID_PUBLISHER {
-                        self.request_sync_time(hardware);
-                        self.negotiate_topics(hardware);
+    /// Handle the frame just completed by `handle_byte`
+    pub(crate) fn dispatch(&mut self, hardware: &mut dyn HardwareInterface) {
+        if self.topic == rosserial_msgs::TOPICINFO_ID_PUBLISHER {
+            self.request_sync_time(hardware);
 
-                        self.configured = true;
-                    }
-                    else if self.topic == rosserial_msgs::TOPICINFO_ID_TIME {
-                        // TODO: sync time
-                    }
-                    else if self.topic == rosserial_msgs::TOPICINFO_ID_TX_STOP {
-                        self.configured = false;
-                    }
-                    else {
-                        let idx = self.topic - 100;
-                        if (idx as usize) < self.subscribers.len() {
-                            if let Some(ref mut sub) = self.subscribers[idx as usize] {
-                                sub.handle_message(&self.message_in[..]);
-                            }
-                        }
+            // Stay disconnected if a topic could not be announced, rather than run without it
+            if self.negotiate_topics(hardware).is_ok() {
+                self.set_connection_state(ConnectionState::Negotiating);
+            }
+        }
+        else if self.topic == rosserial_msgs::TOPICINFO_ID_TIME {
+            self.sync_time(hardware);
+        }
+        else if self.topic == rosserial_msgs::TOPICINFO_ID_TX_STOP {
+            self.set_connection_state(ConnectionState::Disconnected);
+        }
+        else {
+            let idx = self.topic.wrapping_sub(TOPIC_ID_OFFSET as u16) as usize;
+            if idx < self.subscribers.len() {
+                // Take the handler out of its slot so a response can be sent while it is borrowed
+                if let Some(sub) = self.subscribers[idx].take() {
+                    sub.handle_message(&self.message_in[..self.index]);
+
+                    // A response too large to send is counted in the stats, the client times out
+                    if let Some((id, response)) = sub.response() {
+                        self.send_message(id, response, hardware).ok();
                     }
 
-                    self.state = State::Sync;
-                },
+                    self.subscribers[idx] = Some(sub);
+                }
             }
         }
     }
 
-    pub fn request_sync_time(&self, hardware: &mut dyn HardwareInterface) {
+    /// Topic id and payload of the frame just completed by `handle_byte`
+    #[cfg(feature = "async")]
+    pub(crate) fn frame(&self) -> (u16, &[u8]) {
+        (self.topic, &self.message_in[..self.index])
+    }
+
+    /// Largest payload accepted for the given topic id
+    fn max_message_size(&self, topic: u16) -> usize {
+        let idx = topic.wrapping_sub(TOPIC_ID_OFFSET as u16) as usize;
+
+        let buffer_size = match self.subscriber_info.get(idx) {
+            Some(Some((_, ti))) => ti.buffer_size as usize,
+            _ => IN_BUF,
+        };
+
+        buffer_size.min(IN_BUF)
+    }
+
+    pub fn request_sync_time(&mut self, hardware: &mut dyn HardwareInterface) {
+        // Always fits, SIZE_CHECK makes room for a topic info which is larger
         let time = std_msgs::Time::default();
-        self.send_message(rosserial_msgs::TOPICINFO_ID_TIME, &time, hardware)
+        self.send_message(rosserial_msgs::TOPICINFO_ID_TIME, &time, hardware).ok();
+        self.rt_time = hardware.time();
     }
 
-    fn negotiate_topics(&self, hardware: &mut dyn HardwareInterface) {
-        for p in self.publishers.iter() {
-            if let Some(ref p) = p {
-                let ti: rosserial_msgs::TopicInfo = (*p).into();
-                self.send_message(rosserial_msgs::TOPICINFO_ID_PUBLISHER, &ti, hardware);
-            }
+    /// Current ROS time, based on the hardware clock and the offset from the last time sync
+    pub fn now(&self, hardware: &dyn HardwareInterface) -> Time {
+        let nanos = (hardware.time() as i64) * 1_000_000 + self.time_offset;
+        Time::from_nanos(nanos as u64)
+    }
+
+    /// Hardware time (ms) at which the last time sync reply was received
+    pub fn last_sync_time(&self) -> u32 {
+        self.last_sync_receive_time
+    }
+
+    fn sync_time(&mut self, hardware: &mut dyn HardwareInterface) {
+        if self.index < 8 {
+            return;
+        }
+
+        let mut time = std_msgs::Time::default();
+        time.deserialize(&self.message_in[..self.index]);
+
+        // The host stamped its reply roughly half way through the round trip
+        let current_time = hardware.time();
+        let latency = current_time.wrapping_sub(self.rt_time) / 2;
+
+        let ros_nanos = time.data.as_nanos() as i64 + (latency as i64) * 1_000_000;
+        self.time_offset = ros_nanos - (current_time as i64) * 1_000_000;
+        self.last_sync_receive_time = current_time;
+
+        // A reply while disconnected is stale, topics still need to be negotiated
+        if self.connection != ConnectionState::Disconnected {
+            self.set_connection_state(ConnectionState::Connected);
+        }
+    }
+
+    pub(crate) fn check_connection(&mut self, hardware: &mut dyn HardwareInterface) {
+        let current_time = hardware.time();
+
+        match self.connection {
+            ConnectionState::Disconnected => {},
+            ConnectionState::Negotiating => {
+                if current_time.wrapping_sub(self.rt_time) > self.sync_timeout {
+                    self.set_connection_state(ConnectionState::Disconnected);
+                }
+            },
+            ConnectionState::Connected => {
+                if current_time.wrapping_sub(self.last_sync_receive_time) > self.sync_timeout {
+                    self.set_connection_state(ConnectionState::Disconnected);
+                }
+                else if current_time.wrapping_sub(self.rt_time) > self.sync_timeout / 4 {
+                    self.request_sync_time(hardware);
+                }
+            },
+        }
+    }
+
+    pub(crate) fn set_connection_state(&mut self, state: ConnectionState) {
+        if state == self.connection {
+            return;
+        }
+
+        self.connection = state;
+
+        if let Some(ref mut callback) = self.state_callback {
+            callback(state);
+        }
+    }
+
+    fn negotiate_topics(&self, hardware: &mut dyn HardwareInterface) -> Result<(), NodeHandleError> {
+        let mut index = 0;
+        while self.send_topic_info(index, hardware)? {
+            index += 1;
         }
 
-        for ti in self.subscriber_info.iter() {
-            if let Some(ref ti) = ti {
-                self.send_message(rosserial_msgs::TOPICINFO_ID_SUBSCRIBER, ti, hardware);
+        Ok(())
+    }
+
+    /// Announce the publisher or subscriber at `index`, counting publishers first.
+    /// Returns false once `index` is past the last slot, or `MessageTooLarge` if the topic info does not fit
+    /// the output buffer.
+    pub(crate) fn send_topic_info(&self, index: usize, hardware: &mut dyn HardwareInterface) -> Result<bool, NodeHandleError> {
+        if index < MAX_PUBS {
+            if let Some(ref p) = self.publishers[index] {
+                let mut ti: rosserial_msgs::TopicInfo = (*p).into();
+                ti.buffer_size = OUT_BUF as u32;
+                self.send_message(p.endpoint, &ti, hardware)?;
+            }
+        }
+        else if index < MAX_PUBS + MAX_SUBS {
+            if let Some((endpoint, ref ti)) = self.subscriber_info[index - MAX_PUBS] {
+                self.send_message(endpoint, ti, hardware)?;
             }
         }
+        else {
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Stream a frame to the hardware, serializing the message directly rather than into a buffer.
+    /// `OUT_BUF` only limits the frame size, it is not allocated.
+    pub(crate) fn send_message(&self, topic_id: u16, msg: &dyn Message, hardware: &mut dyn HardwareInterface) -> Result<(), NodeHandleError> {
+        let len = msg.serialized_len();
+        if len > OUT_BUF - FRAME_OVERHEAD {
+            self.tx_overflow_errors.set(self.tx_overflow_errors.get() + 1);
+            return Err(NodeHandleError::MessageTooLarge);
+        }
+
+        write_frame(topic_id, msg, len as u16, hardware);
+
+        Ok(())
+    }
+}
+
+/// Stream a frame holding `msg`, whose serialized length is `len`, to the hardware
+pub(crate) fn write_frame(topic_id: u16, msg: &dyn Message, len: u16, hardware: &mut dyn HardwareInterface) {
+    let len_lsb = (len & 0xFF) as u8;
+    let len_msb = ((len >> 8) & 0xFF) as u8;
+
+    hardware.write_all(&[0xFF, PROTOCOL_VER2, len_lsb, len_msb, 255 - len_lsb.wrapping_add(len_msb)]);
+
+    let mut frame = FrameEncoder { hardware, checksum: 0 };
+    frame.write(&topic_id.to_le_bytes());
+    msg.encode(&mut frame);
+
+    let checksum = 255 - frame.checksum;
+    hardware.write(checksum);
+    hardware.flush();
+}
+
+pub(crate) fn read_param_ints(response: &rosserial_msgs::RequestParamResponse, values: &mut [i32]) -> Result<(), NodeHandleError> {
+    if response.ints_len() != values.len() {
+        return Err(NodeHandleError::ParameterMismatch);
+    }
+
+    for (value, param) in values.iter_mut().zip(response.ints()) {
+        *value = param;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn read_param_floats(response: &rosserial_msgs::RequestParamResponse, values: &mut [f32]) -> Result<(), NodeHandleError> {
+    if response.floats_len() != values.len() {
+        return Err(NodeHandleError::ParameterMismatch);
     }
 
-    fn send_message(&self, topic_id: u16, msg: &dyn Message, hardware: &mut dyn HardwareInterface) {
-        let mut message_out: [u8; 256] = [0; 256];
+    for (value, param) in values.iter_mut().zip(response.floats()) {
+        *value = param;
+    }
 
-        let len = msg.serialize(&mut message_out[7..]);
+    Ok(())
+}
 
-        message_out[0] = 0xFF;
-        message_out[1] = PROTOCOL_VER2;
-        message_out[2] = (len & 0xFF) as u8;
-        message_out[3] = ((len >> 8) & 0xFF) as u8;
-        message_out[4] = 255 - ((message_out[2] as u16 + message_out[3] as u16) % 256) as u8;
-        message_out[5] = (topic_id & 0xFF) as u8;
-        message_out[6] = ((topic_id >> 8) & 0xFF) as u8;
+pub(crate) fn read_param_strings<'b>(response: &rosserial_msgs::RequestParamResponse, buf: &'b mut [u8], values: &mut [&'b str]) -> Result<(), NodeHandleError> {
+    if response.strings_len() != values.len() {
+        return Err(NodeHandleError::ParameterMismatch);
+    }
 
-        // TODO: Want to use iterator sum here..
-        let last = 7 + len as usize;
-        let mut checksum: u32 = 0;
-        for b in &message_out[5..last] {
-            checksum += *b as u32;
+    let mut buf = buf;
+    for (value, param) in values.iter_mut().zip(response.strings()) {
+        if param.len() > buf.len() {
+            return Err(NodeHandleError::ParameterMismatch);
         }
-        message_out[last] = (255 - (checksum as u16 % 256)) as u8;
 
-        for data in &message_out[..last+1] {
-            hardware.write(*data);
+        let (dest, rest) = buf.split_at_mut(param.len());
+        dest.copy_from_slice(param.as_bytes());
+        *value = core::str::from_utf8(dest).unwrap_or("");
+        buf = rest;
+    }
+
+    Ok(())
+}
+
+/// Writes message bytes to the hardware, keeping a running checksum
+struct FrameEncoder<'h> {
+    hardware: &'h mut dyn HardwareInterface,
+    checksum: u8,
+}
+
+impl Encoder for FrameEncoder<'_> {
+    fn write(&mut self, data: &[u8]) {
+        for b in data {
+            self.checksum = self.checksum.wrapping_add(*b);
         }
+
+        self.hardware.write_all(data);
     }
 }
@@ -0,0 +1,576 @@
+use core::cell::{Cell, RefCell, RefMut};
+use core::future::{poll_fn, Future};
+use core::ops::{Deref, DerefMut};
+use core::pin::pin;
+use core::task::{Poll, Waker};
+
+use heapless::Vec;
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{Read, Write};
+
+use super::nodehandle::{read_param_floats, read_param_ints, read_param_strings};
+use super::{ConnectionState, HardwareInterface, NodeHandle, NodeHandleError, PublisherHandle, ServiceClient, Time};
+use crate::msgs::{Message, Service, rosserial_msgs};
+
+// How often `run` checks time sync and request timeouts while no data arrives (ms)
+const CHECK_INTERVAL: u32 = 100;
+
+/// Node handle for async executors, over `embedded-io-async` transports.
+///
+/// Topics are set up on a `NodeHandle` first, which is then wrapped. `run` owns the receive half of the
+/// transport and a delay, and must be polled concurrently with the tasks that publish or make requests:
+///
+/// ```ignore
+/// let mut nodehandle: NodeHandle = NodeHandle::default();
+/// let status = nodehandle.advertise::<std_msgs::Bool>("status").unwrap();
+///
+/// let node = AsyncNodeHandle::new(nodehandle, tx, || millis());
+///
+/// join(node.run(&mut rx, &mut Delay), async {
+///     loop {
+///         node.publish(status, &msg).await.ok();
+///         Timer::after_millis(100).await;
+///     }
+/// }).await;
+/// ```
+///
+/// The handle is not `Sync`, all tasks using it must run on the same executor. Outgoing frames are
+/// buffered (up to `OUT_BUF` bytes) so they can be written without holding the node across an await.
+/// One service call or parameter request is outstanding at a time. `run` wakes on its delay every 100ms
+/// without incoming data, to keep time sync going, notice a lost host and time out requests.
+pub struct AsyncNodeHandle<'a, Tx, C, const MAX_PUBS: usize = 25, const MAX_SUBS: usize = 25, const IN_BUF: usize = 512, const OUT_BUF: usize = 512> {
+    node: RefCell<NodeHandle<'a, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>>,
+    tx: Lock<Tx>,
+    clock: C,
+
+    // Held for the duration of a service call or parameter request
+    request: Lock<()>,
+    // Topic id the outstanding request is waiting on, and the reply once received
+    reply_topic: Cell<Option<u16>>,
+    reply_len: Cell<Option<usize>>,
+    reply: RefCell<[u8; IN_BUF]>,
+    // Task waiting in `request`, woken by `run`
+    reply_waker: RefCell<Option<Waker>>,
+}
+
+impl<'a, Tx, C, const MAX_PUBS: usize, const MAX_SUBS: usize, const IN_BUF: usize, const OUT_BUF: usize> AsyncNodeHandle<'a, Tx, C, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>
+where
+    Tx: Write,
+    C: Fn() -> u32,
+{
+    pub fn new(node: NodeHandle<'a, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>, tx: Tx, clock: C) -> Self {
+        AsyncNodeHandle {
+            node: RefCell::new(node),
+            tx: Lock::new(tx),
+            clock,
+
+            request: Lock::new(()),
+            reply_topic: Cell::new(None),
+            reply_len: Cell::new(None),
+            reply: RefCell::new([0; IN_BUF]),
+            reply_waker: RefCell::new(None),
+        }
+    }
+
+    /// Access the wrapped node handle, e.g. to advertise more topics. Must not be held across an await
+    pub fn node(&self) -> RefMut<'_, NodeHandle<'a, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>> {
+        self.node.borrow_mut()
+    }
+
+    pub fn connected(&self) -> bool {
+        self.node.borrow().connected()
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        self.node.borrow().connection_state()
+    }
+
+    pub fn now(&self) -> Time {
+        let hardware = FrameBuffer::new(&mut [], &self.clock);
+        self.node.borrow().now(&hardware)
+    }
+
+    /// Receive loop. Reads from `rx`, runs subscriber and service callbacks and answers the host, and
+    /// checks the connection on `delay` while no data arrives.
+    /// Returns once `rx` reaches end of file or fails, leaving the node disconnected.
+    pub async fn run<Rx: Read, D: DelayNs>(&self, rx: &mut Rx, delay: &mut D) -> Result<(), Rx::Error> {
+        let mut buf = [0u8; 64];
+
+        loop {
+            let result = {
+                // Keep the same read across checks, rather than cancel it and risk losing data
+                let mut read = pin!(rx.read(&mut buf));
+
+                loop {
+                    let mut tick = pin!(delay.delay_ms(CHECK_INTERVAL));
+                    let event = poll_fn(|cx| match read.as_mut().poll(cx) {
+                        Poll::Ready(result) => Poll::Ready(Some(result)),
+                        Poll::Pending => tick.as_mut().poll(cx).map(|_| None),
+                    }).await;
+
+                    match event {
+                        Some(result) => break result,
+                        None => {
+                            self.check_connection().await;
+                            self.wake_request();
+                        },
+                    }
+                }
+            };
+
+            let n = match result {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    self.node.borrow_mut().set_connection_state(ConnectionState::Disconnected);
+                    self.wake_request();
+                    return Err(e);
+                },
+            };
+
+            for data in &buf[..n] {
+                let complete = self.node.borrow_mut().handle_byte(*data);
+                if complete {
+                    self.handle_frame().await;
+                }
+            }
+
+            self.check_connection().await;
+        }
+
+        self.node.borrow_mut().set_connection_state(ConnectionState::Disconnected);
+        self.wake_request();
+
+        Ok(())
+    }
+
+    /// Check for loss of time sync, and request time sync when due.
+    /// `run` does this whenever data arrives and on each check interval, as does publishing.
+    pub async fn check_connection(&self) {
+        let mut frame = [0u8; OUT_BUF];
+        let len = self.with_frame_buffer(&mut frame, |node, hardware| node.check_connection(hardware));
+        self.write_frame(&frame[..len]).await;
+    }
+
+    pub async fn publish<Msg: Message>(&self, handle: PublisherHandle<Msg>, msg: &Msg) -> Result<(), NodeHandleError> {
+        self.check_connection().await;
+
+        let mut frame = [0u8; OUT_BUF];
+        let mut result = Ok(());
+        let len = self.with_frame_buffer(&mut frame, |node, hardware| result = node.publish(handle, msg, hardware));
+        result?;
+
+        self.write_frame(&frame[..len]).await;
+
+        Ok(())
+    }
+
+    /// Call a service on the host, waiting up to `timeout` (ms) for the response
+    pub async fn call<Srv: Service>(&self, client: &ServiceClient<Srv>, request: &Srv::Request, timeout: u32) -> Result<Srv::Response, NodeHandleError> {
+        let len = self.request(client.request_id, request, client.response_id, timeout).await?;
+
+        let mut response = Srv::Response::default();
+        response.deserialize(&self.reply.borrow()[..len]);
+
+        Ok(response)
+    }
+
+    pub async fn get_param_int(&self, name: &str, timeout: u32) -> Result<i32, NodeHandleError> {
+        let mut value = [0];
+        self.get_param_ints(name, &mut value, timeout).await?;
+        Ok(value[0])
+    }
+
+    pub async fn get_param_float(&self, name: &str, timeout: u32) -> Result<f32, NodeHandleError> {
+        let mut value = [0.0];
+        self.get_param_floats(name, &mut value, timeout).await?;
+        Ok(value[0])
+    }
+
+    /// Read a string parameter into `buf`, returning the part of `buf` that was used
+    pub async fn get_param_string<'b>(&self, name: &str, buf: &'b mut [u8], timeout: u32) -> Result<&'b str, NodeHandleError> {
+        let mut value = [""];
+        self.get_param_strings(name, buf, &mut value, timeout).await?;
+        Ok(value[0])
+    }
+
+    /// Read an integer array parameter. The parameter must have exactly `values.len()` elements
+    pub async fn get_param_ints(&self, name: &str, values: &mut [i32], timeout: u32) -> Result<(), NodeHandleError> {
+        let len = self.request_param(name, timeout).await?;
+
+        let reply = self.reply.borrow();
+        let response = rosserial_msgs::RequestParamResponse::parse(&reply[..len]).ok_or(NodeHandleError::ParameterMismatch)?;
+        read_param_ints(&response, values)
+    }
+
+    /// Read a float array parameter. The parameter must have exactly `values.len()` elements
+    pub async fn get_param_floats(&self, name: &str, values: &mut [f32], timeout: u32) -> Result<(), NodeHandleError> {
+        let len = self.request_param(name, timeout).await?;
+
+        let reply = self.reply.borrow();
+        let response = rosserial_msgs::RequestParamResponse::parse(&reply[..len]).ok_or(NodeHandleError::ParameterMismatch)?;
+        read_param_floats(&response, values)
+    }
+
+    /// Read a string array parameter. The parameter must have exactly `values.len()` elements.
+    /// The strings are copied into `buf` and `values` is set to point at each of them.
+    pub async fn get_param_strings<'b>(&self, name: &str, buf: &'b mut [u8], values: &mut [&'b str], timeout: u32) -> Result<(), NodeHandleError> {
+        let len = self.request_param(name, timeout).await?;
+
+        let reply = self.reply.borrow();
+        let response = rosserial_msgs::RequestParamResponse::parse(&reply[..len]).ok_or(NodeHandleError::ParameterMismatch)?;
+        read_param_strings(&response, buf, values)
+    }
+
+    async fn request_param(&self, name: &str, timeout: u32) -> Result<usize, NodeHandleError> {
+        let request = rosserial_msgs::RequestParamRequest { name };
+        let topic = rosserial_msgs::TOPICINFO_ID_PARAMETER_REQUEST;
+
+        self.request(topic, &request, topic, timeout).await
+    }
+
+    /// Send a request and wait for the reply on `reply_topic`, returning the length of the reply
+    async fn request(&self, topic: u16, request: &dyn Message, reply_topic: u16, timeout: u32) -> Result<usize, NodeHandleError> {
+        let _request = self.request.lock().await;
+
+        if !self.connected() {
+            return Err(NodeHandleError::NotConfigured);
+        }
+
+        // Set before sending, the reply may arrive while the request is being written
+        self.reply_len.set(None);
+        self.reply_topic.set(Some(reply_topic));
+
+        let mut frame = [0u8; OUT_BUF];
+        let mut result = Ok(());
+        let len = self.with_frame_buffer(&mut frame, |node, hardware| result = node.send_message(topic, request, hardware));
+
+        if let Err(e) = result {
+            self.reply_topic.set(None);
+            return Err(e);
+        }
+
+        self.write_frame(&frame[..len]).await;
+
+        let start = (self.clock)();
+
+        let result = poll_fn(|cx| {
+            if let Some(len) = self.reply_len.take() {
+                Poll::Ready(Ok(len))
+            }
+            else if !self.connected() {
+                Poll::Ready(Err(NodeHandleError::NotConfigured))
+            }
+            else if (self.clock)().wrapping_sub(start) >= timeout {
+                Poll::Ready(Err(NodeHandleError::Timeout))
+            }
+            else {
+                // `run` wakes this on the reply, and on each check so the timeout is noticed
+                *self.reply_waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }).await;
+
+        if result.is_err() {
+            self.reply_topic.set(None);
+        }
+
+        result
+    }
+
+    fn wake_request(&self) {
+        if let Some(waker) = self.reply_waker.take() {
+            waker.wake();
+        }
+    }
+
+    async fn handle_frame(&self) {
+        let topic = self.node.borrow().frame().0;
+
+        if topic == rosserial_msgs::TOPICINFO_ID_PUBLISHER {
+            self.negotiate().await;
+        }
+        else if self.reply_topic.get() == Some(topic) {
+            let node = self.node.borrow();
+            let (_, payload) = node.frame();
+
+            self.reply.borrow_mut()[..payload.len()].copy_from_slice(payload);
+            self.reply_len.set(Some(payload.len()));
+            self.reply_topic.set(None);
+            self.wake_request();
+        }
+        else {
+            // Everything else sends at most one frame in reply
+            let mut frame = [0u8; OUT_BUF];
+            let len = self.with_frame_buffer(&mut frame, |node, hardware| node.dispatch(hardware));
+            self.write_frame(&frame[..len]).await;
+        }
+    }
+
+    /// Announce topics one frame at a time, rather than all at once as `spin_once` does
+    async fn negotiate(&self) {
+        let mut frame = [0u8; OUT_BUF];
+
+        let len = self.with_frame_buffer(&mut frame, |node, hardware| node.request_sync_time(hardware));
+        self.write_frame(&frame[..len]).await;
+
+        let mut index = 0;
+        loop {
+            let mut more = Ok(false);
+            let len = self.with_frame_buffer(&mut frame, |node, hardware| more = node.send_topic_info(index, hardware));
+
+            match more {
+                Ok(true) => {},
+                Ok(false) => break,
+                // Stay disconnected if a topic could not be announced, rather than run without it
+                Err(_) => return,
+            }
+
+            self.write_frame(&frame[..len]).await;
+            index += 1;
+        }
+
+        self.node.borrow_mut().set_connection_state(ConnectionState::Negotiating);
+    }
+
+    /// Run `f` with the node and a hardware interface that captures written frames into `frame`.
+    /// Returns the number of bytes written.
+    fn with_frame_buffer<F>(&self, frame: &mut [u8; OUT_BUF], f: F) -> usize
+    where
+        F: FnOnce(&mut NodeHandle<'a, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>, &mut dyn HardwareInterface),
+    {
+        let mut hardware = FrameBuffer::new(frame, &self.clock);
+        f(&mut self.node.borrow_mut(), &mut hardware);
+        hardware.len
+    }
+
+    // The tx lock keeps frames from interleaving
+    async fn write_frame(&self, frame: &[u8]) {
+        if frame.is_empty() {
+            return;
+        }
+
+        let mut tx = self.tx.lock().await;
+        tx.write_all(frame).await.ok();
+        tx.flush().await.ok();
+    }
+}
+
+/// Hardware interface handed to the synchronous node handle, capturing written bytes
+struct FrameBuffer<'b, C> {
+    buf: &'b mut [u8],
+    len: usize,
+    clock: &'b C,
+}
+
+impl<'b, C: Fn() -> u32> FrameBuffer<'b, C> {
+    fn new(buf: &'b mut [u8], clock: &'b C) -> Self {
+        FrameBuffer {
+            buf,
+            len: 0,
+            clock,
+        }
+    }
+}
+
+impl<C: Fn() -> u32> HardwareInterface for FrameBuffer<'_, C> {
+    fn read(&mut self) -> Option<u8> {
+        None
+    }
+
+    fn write(&mut self, data: u8) {
+        // Frames are limited to `OUT_BUF` by the node handle, so this never drops bytes
+        if self.len < self.buf.len() {
+            self.buf[self.len] = data;
+            self.len += 1;
+        }
+    }
+
+    fn time(&self) -> u32 {
+        (self.clock)()
+    }
+}
+
+/// Tasks waiting on a `Lock` beyond this are polled again straight away rather than tracked
+const MAX_WAITERS: usize = 8;
+
+/// Async mutex for tasks on a single executor. Waiting tasks are woken when the lock is released
+struct Lock<T> {
+    value: RefCell<T>,
+    waiters: RefCell<Vec<Waker, MAX_WAITERS>>,
+}
+
+impl<T> Lock<T> {
+    fn new(value: T) -> Self {
+        Lock {
+            value: RefCell::new(value),
+            waiters: RefCell::new(Vec::new()),
+        }
+    }
+
+    async fn lock(&self) -> LockGuard<'_, T> {
+        poll_fn(|cx| match self.value.try_borrow_mut() {
+            Ok(value) => Poll::Ready(LockGuard { value, waiters: &self.waiters }),
+            Err(_) => {
+                let mut waiters = self.waiters.borrow_mut();
+                if !waiters.iter().any(|w| w.will_wake(cx.waker())) && waiters.push(cx.waker().clone()).is_err() {
+                    cx.waker().wake_by_ref();
+                }
+
+                Poll::Pending
+            },
+        }).await
+    }
+}
+
+struct LockGuard<'l, T> {
+    value: RefMut<'l, T>,
+    waiters: &'l RefCell<Vec<Waker, MAX_WAITERS>>,
+}
+
+impl<T> Deref for LockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for LockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for LockGuard<'_, T> {
+    fn drop(&mut self) {
+        // Waking only schedules the waiters, the value is released before any of them runs
+        let waiters = core::mem::take(&mut *self.waiters.borrow_mut());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::boxed::Box;
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use std::task::{Context, Wake};
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::msgs::std_msgs;
+    use crate::testing::Frame;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Reads the queued bytes, then waits forever as a host that went away would
+    struct SilentRx(VecDeque<u8>);
+
+    impl embedded_io_async::ErrorType for SilentRx {
+        type Error = Infallible;
+    }
+
+    impl Read for SilentRx {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            if self.0.is_empty() {
+                return core::future::pending().await;
+            }
+
+            let n = buf.len().min(self.0.len());
+            for (b, data) in buf.iter_mut().zip(self.0.drain(..n)) {
+                *b = data;
+            }
+            Ok(n)
+        }
+    }
+
+    struct TxLog<'t>(&'t RefCell<Vec<u8>>);
+
+    impl embedded_io_async::ErrorType for TxLog<'_> {
+        type Error = Infallible;
+    }
+
+    impl Write for TxLog<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    /// Completes on the next poll, advancing `clock` by the delay
+    struct MockDelay<'c>(&'c Cell<u32>);
+
+    impl DelayNs for MockDelay<'_> {
+        async fn delay_ns(&mut self, ns: u32) {
+            let mut waited = false;
+            poll_fn(|cx| {
+                if waited {
+                    Poll::Ready(())
+                }
+                else {
+                    waited = true;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }).await;
+
+            self.0.set(self.0.get() + ns / 1_000_000);
+        }
+    }
+
+    #[test]
+    fn checks_connection_without_data() {
+        let time = Cell::new(0);
+        let tx = RefCell::new(Vec::new());
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.set_sync_timeout(1000);
+        let node = AsyncNodeHandle::new(nodehandle, TxLog(&tx), || time.get());
+
+        let reply = std_msgs::Time { data: Time::new(5, 0) };
+        let mut rx = SilentRx([
+            Frame::new(rosserial_msgs::TOPICINFO_ID_PUBLISHER, &[]).to_bytes(),
+            Frame::message(rosserial_msgs::TOPICINFO_ID_TIME, &reply).to_bytes(),
+        ].concat().into());
+        let mut delay = MockDelay(&time);
+
+        let waker = Arc::new(NoopWaker).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut run = Box::pin(node.run(&mut rx, &mut delay));
+
+        assert!(run.as_mut().poll(&mut cx).is_pending());
+        assert!(node.connected());
+
+        // Each poll of `run` is one check interval, with no data arriving
+        let mut request = Box::pin(node.get_param_int("gain", 500));
+        let result = loop {
+            assert!(time.get() < 1000, "request did not time out");
+            if let Poll::Ready(result) = request.as_mut().poll(&mut cx) {
+                break result;
+            }
+            assert!(run.as_mut().poll(&mut cx).is_pending());
+        };
+        assert!(matches!(result, Err(NodeHandleError::Timeout)));
+        assert!(time.get() >= 500);
+
+        tx.borrow_mut().clear();
+        while node.connected() {
+            assert!(time.get() < 2000, "node did not lose sync");
+            assert!(run.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(node.connection_state(), ConnectionState::Disconnected);
+        assert!(time.get() > 1000);
+
+        // Time sync was requested while waiting
+        let mut written = tx.borrow().clone();
+        assert!(crate::host::take_frame(&mut written).is_some_and(|(topic, _)| topic == rosserial_msgs::TOPICINFO_ID_TIME));
+    }
+}
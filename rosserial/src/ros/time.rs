@@ -1,5 +1,50 @@
-#[derive(Default)]
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
 pub struct Time {
     pub sec: u32,
     pub nsec: u32,
 }
+
+impl Time {
+    pub fn new(sec: u32, nsec: u32) -> Self {
+        Time::from_nanos(sec as u64 * NSEC_PER_SEC + nsec as u64)
+    }
+
+    /// Create a time from nanoseconds since the epoch, wrapping the seconds field
+    pub fn from_nanos(nanos: u64) -> Self {
+        Time {
+            sec: (nanos / NSEC_PER_SEC) as u32,
+            nsec: (nanos % NSEC_PER_SEC) as u32,
+        }
+    }
+
+    pub fn as_nanos(&self) -> u64 {
+        self.sec as u64 * NSEC_PER_SEC + self.nsec as u64
+    }
+}
+
+/// Signed span of time, as ROS's `duration` field type
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct Duration {
+    pub sec: i32,
+    pub nsec: i32,
+}
+
+impl Duration {
+    pub fn new(sec: i32, nsec: i32) -> Self {
+        Duration::from_nanos(sec as i64 * NSEC_PER_SEC as i64 + nsec as i64)
+    }
+
+    /// Create a duration from nanoseconds, normalizing `nsec` into 0..1s as ROS does
+    pub fn from_nanos(nanos: i64) -> Self {
+        Duration {
+            sec: nanos.div_euclid(NSEC_PER_SEC as i64) as i32,
+            nsec: nanos.rem_euclid(NSEC_PER_SEC as i64) as i32,
+        }
+    }
+
+    pub fn as_nanos(&self) -> i64 {
+        self.sec as i64 * NSEC_PER_SEC as i64 + self.nsec as i64
+    }
+}
@@ -1,5 +1,5 @@
-use crate::msgs::rosserial_msgs::TopicInfo;
-use crate::msgs::Message;
+use crate::msgs::rosserial_msgs::{self, TopicInfo};
+use crate::msgs::{Message, Service};
 
 #[derive(Clone, Copy)]
 pub struct Publisher {
@@ -7,6 +7,7 @@ pub struct Publisher {
     pub id: u16,
     pub message_type: &'static str,
     pub md5sum: &'static str,
+    pub endpoint: u16,
 }
 
 impl Publisher {
@@ -16,17 +17,18 @@ impl Publisher {
             id,
             message_type,
             md5sum,
+            endpoint: rosserial_msgs::TOPICINFO_ID_PUBLISHER,
         }
     }
 }
 
-impl Into<TopicInfo> for Publisher {
-    fn into(self) -> TopicInfo {
+impl From<Publisher> for TopicInfo<'static> {
+    fn from(publisher: Publisher) -> Self {
         TopicInfo {
-            id: self.id,
-            name: self.topic,
-            message_type: self.message_type,
-            md5: self.md5sum,
+            id: publisher.id,
+            name: publisher.topic,
+            message_type: publisher.message_type,
+            md5: publisher.md5sum,
             buffer_size: 256,
         }
     }
@@ -34,12 +36,22 @@ impl Into<TopicInfo> for Publisher {
 
 pub trait MessageHandler {
     fn handle_message(&mut self, data: &[u8]);
+
+    /// Message to publish in reply to the last handled message, and the topic id to publish it on
+    fn response(&self) -> Option<(u16, &dyn Message)> {
+        None
+    }
 }
 
 pub trait TopicBase {
     fn topic(&self) -> &'static str;
     fn message_type(&self) -> &'static str;
     fn md5sum(&self) -> &'static str;
+
+    /// Topic info id used to announce this topic during negotiation
+    fn endpoint(&self) -> u16 {
+        rosserial_msgs::TOPICINFO_ID_SUBSCRIBER
+    }
 }
 
 pub struct Subscriber<Msg, F: FnMut(Msg)> {
@@ -86,6 +98,84 @@ impl<Msg: Message, F: FnMut(Msg)> TopicBase for Subscriber<Msg, F> {
     }
 }
 
+pub struct ServiceServer<Srv: Service, F: FnMut(&Srv::Request, &mut Srv::Response)> {
+    pub topic: &'static str,
+    pub(crate) response_id: Option<u16>,
+
+    response: Srv::Response,
+    callback: F,
+}
+
+impl<Srv: Service, F: FnMut(&Srv::Request, &mut Srv::Response)> ServiceServer<Srv, F> {
+    pub fn new(topic: &'static str, callback: F) -> Self {
+        ServiceServer {
+            topic,
+            response_id: None,
+            response: Default::default(),
+            callback,
+        }
+    }
+
+    /// Topic info for the response publisher paired with this service
+    pub(crate) fn response_info(&self, id: u16) -> Publisher {
+        let mut p = Publisher::new(self.topic, id, Srv::name(), Srv::Response::md5());
+        p.endpoint = rosserial_msgs::TOPICINFO_ID_SERVICE_SERVER + rosserial_msgs::TOPICINFO_ID_PUBLISHER;
+        p
+    }
+}
+
+impl<Srv: Service, F: FnMut(&Srv::Request, &mut Srv::Response)> MessageHandler for ServiceServer<Srv, F> {
+    fn handle_message(&mut self, data: &[u8]) {
+        let mut request = Srv::Request::default();
+        request.deserialize(data);
+
+        self.response = Srv::Response::default();
+        (self.callback)(&request, &mut self.response);
+    }
+
+    fn response(&self) -> Option<(u16, &dyn Message)> {
+        self.response_id.map(|id| (id, &self.response as &dyn Message))
+    }
+}
+
+impl<Srv: Service, F: FnMut(&Srv::Request, &mut Srv::Response)> TopicBase for ServiceServer<Srv, F> {
+    fn topic(&self) -> &'static str {
+        self.topic
+    }
+
+    fn message_type(&self) -> &'static str {
+        Srv::name()
+    }
+
+    fn md5sum(&self) -> &'static str {
+        Srv::Request::md5()
+    }
+
+    fn endpoint(&self) -> u16 {
+        rosserial_msgs::TOPICINFO_ID_SERVICE_SERVER + rosserial_msgs::TOPICINFO_ID_SUBSCRIBER
+    }
+}
+
+/// Handle to a service on the host, created by `NodeHandle::service_client`
+pub struct ServiceClient<Srv: Service> {
+    pub topic: &'static str,
+    pub(crate) request_id: u16,
+    pub(crate) response_id: u16,
+
+    phantom_srv: core::marker::PhantomData<Srv>,
+}
+
+impl<Srv: Service> ServiceClient<Srv> {
+    pub(crate) fn new(topic: &'static str, request_id: u16, response_id: u16) -> Self {
+        ServiceClient {
+            topic,
+            request_id,
+            response_id,
+            phantom_srv: Default::default(),
+        }
+    }
+}
+
 // impl Into<TopicInfo> for Subscriber {
 //     fn into(self) -> TopicInfo {
 //         TopicInfo {
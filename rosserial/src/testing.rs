@@ -0,0 +1,651 @@
+//! Mock hardware and a scripted rosserial host, for testing code built on `NodeHandle` without a device.
+//!
+//! ```ignore
+//! let mut nodehandle: NodeHandle = NodeHandle::default();
+//! let status = nodehandle.advertise::<std_msgs::Bool>("status").unwrap();
+//! nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut led).unwrap();
+//!
+//! let mut host = MockHost::new();
+//! assert!(host.connect(&mut nodehandle));
+//!
+//! host.publish("led", &std_msgs::Bool { data: true });
+//! host.spin(&mut nodehandle);
+//!
+//! nodehandle.publish(status, &std_msgs::Bool { data: true }, &mut host.hardware).unwrap();
+//! host.spin(&mut nodehandle);
+//! assert_eq!(host.received::<std_msgs::Bool>("status").len(), 1);
+//! ```
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::host::{self, DeviceTopic};
+use crate::msgs::{Message, rosserial_msgs, std_msgs};
+use crate::ros::{self, HardwareInterface, NodeHandle};
+
+/// One rosserial frame: a topic id and serialized payload
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub topic: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(topic: u16, payload: &[u8]) -> Self {
+        Frame {
+            topic,
+            payload: payload.to_vec(),
+        }
+    }
+
+    pub fn message(topic: u16, msg: &dyn Message) -> Self {
+        let mut payload = std::vec![0u8; msg.serialized_len()];
+        msg.serialize(&mut payload);
+
+        Frame {
+            topic,
+            payload,
+        }
+    }
+
+    /// Deserialize the payload as `Msg`
+    pub fn decode<Msg: Message + Default>(&self) -> Msg {
+        let mut msg = Msg::default();
+        msg.deserialize(&self.payload);
+        msg
+    }
+
+    /// Encode as it appears on the wire, header and checksums included
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = self.payload.len() as u16;
+        let len_bytes = len.to_le_bytes();
+        let topic_bytes = self.topic.to_le_bytes();
+
+        let mut bytes = std::vec![0xFF, 0xFE, len_bytes[0], len_bytes[1], checksum(&len_bytes), topic_bytes[0], topic_bytes[1]];
+        bytes.extend_from_slice(&self.payload);
+        bytes.push(checksum(&bytes[5..]));
+
+        bytes
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    255 - data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+/// `HardwareInterface` that replays scripted input, records output, and has a clock under test control
+#[derive(Default)]
+pub struct MockHardware {
+    rx: VecDeque<u8>,
+    tx: Vec<u8>,
+    time: Cell<u32>,
+    time_step: u32,
+}
+
+impl MockHardware {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue bytes for the node to read
+    pub fn feed(&mut self, data: &[u8]) {
+        self.rx.extend(data);
+    }
+
+    pub fn feed_frame(&mut self, frame: &Frame) {
+        self.feed(&frame.to_bytes());
+    }
+
+    /// Number of queued bytes not yet read
+    pub fn pending(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Everything written since the last `take_written`
+    pub fn written(&self) -> &[u8] {
+        &self.tx
+    }
+
+    pub fn take_written(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.tx)
+    }
+
+    /// Take everything written as frames. Panics if the output is not well formed
+    pub fn take_frames(&mut self) -> Vec<Frame> {
+        let mut written = self.take_written();
+        let len = written.len();
+
+        let mut frames = Vec::new();
+        while let Some((topic, payload)) = host::take_frame(&mut written) {
+            frames.push(Frame { topic, payload });
+        }
+
+        let framed: usize = frames.iter().map(|f| f.payload.len() + 8).sum();
+        assert_eq!(framed, len, "node wrote bytes outside well formed frames");
+
+        frames
+    }
+
+    pub fn set_time(&self, time: u32) {
+        self.time.set(time);
+    }
+
+    pub fn advance(&self, ms: u32) {
+        self.time.set(self.time.get().wrapping_add(ms));
+    }
+
+    /// Advance the clock by `ms` every time it is read, so blocking calls with a timeout terminate
+    pub fn set_time_step(&mut self, ms: u32) {
+        self.time_step = ms;
+    }
+}
+
+impl HardwareInterface for MockHardware {
+    fn read(&mut self) -> Option<u8> {
+        self.rx.pop_front()
+    }
+
+    fn write(&mut self, data: u8) {
+        self.tx.push(data);
+    }
+
+    fn time(&self) -> u32 {
+        let time = self.time.get();
+        self.advance(self.time_step);
+        time
+    }
+}
+
+/// Scripted rosserial host.
+///
+/// Requests topics, answers time sync requests with its own clock, and collects everything else the
+/// node sends so tests can inspect it.
+#[derive(Default)]
+pub struct MockHost {
+    pub hardware: MockHardware,
+
+    /// Announcements, with the topic id each was sent on, e.g. `TOPICINFO_ID_PUBLISHER`
+    topics: Vec<(u16, DeviceTopic)>,
+    received: Vec<Frame>,
+    time: ros::Time,
+}
+
+impl MockHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the ROS time sent in time sync replies
+    pub fn set_time(&mut self, time: ros::Time) {
+        self.time = time;
+    }
+
+    /// Request topics and sync time, as `serial_node.py` does on connect. Returns whether the node connected
+    pub fn connect<const MAX_PUBS: usize, const MAX_SUBS: usize, const IN_BUF: usize, const OUT_BUF: usize>(&mut self, nodehandle: &mut NodeHandle<'_, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>) -> bool {
+        self.topics.clear();
+        self.hardware.feed_frame(&Frame::new(rosserial_msgs::TOPICINFO_ID_PUBLISHER, &[]));
+        self.spin(nodehandle);
+
+        nodehandle.connected()
+    }
+
+    /// Run the node until it has read all queued input, answering protocol requests as they appear
+    pub fn spin<const MAX_PUBS: usize, const MAX_SUBS: usize, const IN_BUF: usize, const OUT_BUF: usize>(&mut self, nodehandle: &mut NodeHandle<'_, MAX_PUBS, MAX_SUBS, IN_BUF, OUT_BUF>) {
+        loop {
+            while self.hardware.pending() > 0 {
+                nodehandle.spin_once(&mut self.hardware);
+            }
+
+            let frames = self.hardware.take_frames();
+            if frames.is_empty() {
+                break;
+            }
+
+            for frame in frames {
+                self.handle_frame(frame);
+            }
+
+            // Nothing to answer, so the node has nothing more to read
+            if self.hardware.pending() == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Send `msg` to the node's subscriber on `topic`. Panics if the node did not announce one
+    pub fn publish<Msg: Message>(&mut self, topic: &str, msg: &Msg) {
+        let id = self.topic(rosserial_msgs::TOPICINFO_ID_SUBSCRIBER, topic)
+            .unwrap_or_else(|| panic!("no subscriber on {}", topic))
+            .id;
+
+        self.hardware.feed_frame(&Frame::message(id, msg));
+    }
+
+    /// Send a raw frame to the node
+    pub fn send(&mut self, frame: &Frame) {
+        self.hardware.feed_frame(frame);
+    }
+
+    /// Ask the node to stop, as the host does on shutdown
+    pub fn disconnect(&mut self) {
+        self.hardware.feed_frame(&Frame::new(rosserial_msgs::TOPICINFO_ID_TX_STOP, &[]));
+    }
+
+    /// Everything announced by the node during the last negotiation, with the topic id each announcement
+    /// was sent on
+    pub fn topics(&self) -> &[(u16, DeviceTopic)] {
+        &self.topics
+    }
+
+    /// Find an announcement by the topic id it was sent on and topic name
+    pub fn topic(&self, endpoint: u16, name: &str) -> Option<&DeviceTopic> {
+        self.topics.iter()
+            .find(|(e, t)| *e == endpoint && t.name == name)
+            .map(|(_, t)| t)
+    }
+
+    /// Messages published by the node on `topic` since the last `take_received`
+    pub fn received<Msg: Message + Default>(&self, topic: &str) -> Vec<Msg> {
+        let id = match self.topic(rosserial_msgs::TOPICINFO_ID_PUBLISHER, topic) {
+            Some(t) => t.id,
+            None => return Vec::new(),
+        };
+
+        self.received.iter()
+            .filter(|f| f.topic == id)
+            .map(|f| f.decode())
+            .collect()
+    }
+
+    /// Log lines sent by the node, as (level, message)
+    pub fn logs(&self) -> Vec<(u8, String)> {
+        self.received.iter()
+            .filter(|f| f.topic == rosserial_msgs::TOPICINFO_ID_LOG)
+            .filter_map(|f| rosserial_msgs::Log::parse(&f.payload))
+            .map(|log| (log.level, log.msg.to_string()))
+            .collect()
+    }
+
+    /// Take every non-protocol frame received from the node: publications, logs, service and parameter requests
+    pub fn take_received(&mut self) -> Vec<Frame> {
+        std::mem::take(&mut self.received)
+    }
+
+    fn handle_frame(&mut self, frame: Frame) {
+        match frame.topic {
+            rosserial_msgs::TOPICINFO_ID_TIME => {
+                let reply = std_msgs::Time { data: self.time };
+                self.hardware.feed_frame(&Frame::message(rosserial_msgs::TOPICINFO_ID_TIME, &reply));
+            },
+            endpoint if endpoint < rosserial_msgs::TOPICINFO_ID_PARAMETER_REQUEST => {
+                let info = rosserial_msgs::TopicInfo::parse(&frame.payload).expect("malformed topic info");
+                let topic = DeviceTopic::from(info);
+
+                // Announcements are repeated on each negotiation
+                match self.topics.iter_mut().find(|(e, t)| *e == endpoint && t.id == topic.id) {
+                    Some((_, t)) => *t = topic,
+                    None => self.topics.push((endpoint, topic)),
+                }
+            },
+            _ => self.received.push(frame),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::host::Host;
+    use crate::msgs::Service;
+    use crate::ros::{ConnectionState, NodeHandleError, ServiceServer, Subscriber, Time};
+
+    struct AddOne;
+
+    impl Service for AddOne {
+        type Request = std_msgs::Int32;
+        type Response = std_msgs::Int32;
+
+        fn name() -> &'static str {
+            "test_msgs/AddOne"
+        }
+    }
+
+    /// Serialized `RequestParamResponse` holding only ints
+    fn int_params(ints: &[i32]) -> Vec<u8> {
+        let mut payload = (ints.len() as u32).to_le_bytes().to_vec();
+        for i in ints {
+            payload.extend_from_slice(&i.to_le_bytes());
+        }
+        payload.extend_from_slice(&[0; 8]);
+        payload
+    }
+
+    /// Pass frames between `host` and a node on `device` until both are idle, returning those the host sent
+    fn exchange(host: &mut Host<MockHardware>, nodehandle: &mut NodeHandle, device: &mut MockHardware) -> Vec<Frame> {
+        let mut sent = Vec::new();
+
+        loop {
+            host.spin_once();
+            let frames = host.hardware().take_frames();
+            for frame in frames.iter() {
+                device.feed_frame(frame);
+            }
+
+            nodehandle.spin_once(device);
+            while device.pending() > 0 {
+                nodehandle.spin_once(device);
+            }
+
+            let replies = device.take_written();
+            if frames.is_empty() && replies.is_empty() {
+                return sent;
+            }
+
+            host.hardware().feed(&replies);
+            sent.extend(frames);
+        }
+    }
+
+    fn topic_requests(frames: &[Frame]) -> usize {
+        frames.iter().filter(|f| f.topic == rosserial_msgs::TOPICINFO_ID_PUBLISHER).count()
+    }
+
+    #[test]
+    fn negotiation_announces_topics_and_syncs_time() {
+        let mut led = Subscriber::new("led", |_: std_msgs::Bool| {});
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.advertise::<std_msgs::Bool>("status").unwrap();
+        nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut led).unwrap();
+
+        let mut host = MockHost::new();
+        host.set_time(Time::new(100, 0));
+        assert!(host.connect(&mut nodehandle));
+
+        let status = host.topic(rosserial_msgs::TOPICINFO_ID_PUBLISHER, "status").unwrap();
+        assert_eq!(status.message_type, "std_msgs/Bool");
+        assert_eq!(status.md5, "8b94c1b53db61fb6aed406028ad6332a");
+
+        let led = host.topic(rosserial_msgs::TOPICINFO_ID_SUBSCRIBER, "led").unwrap();
+        assert_eq!(led.buffer_size, 512);
+
+        assert_eq!(nodehandle.now(&host.hardware).sec, 100);
+    }
+
+    #[test]
+    fn time_sync_accounts_for_round_trip() {
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+
+        // The reply arrives 40ms after the request, so the host stamped it 20ms ago
+        host.hardware.set_time(5000);
+        nodehandle.request_sync_time(&mut host.hardware);
+        host.hardware.take_frames();
+
+        host.hardware.advance(40);
+        host.send(&Frame::message(rosserial_msgs::TOPICINFO_ID_TIME, &std_msgs::Time { data: Time::new(200, 0) }));
+        host.spin(&mut nodehandle);
+
+        assert_eq!(nodehandle.last_sync_time(), 5040);
+        assert_eq!(nodehandle.now(&host.hardware), Time::new(200, 20_000_000));
+
+        host.hardware.advance(1500);
+        assert_eq!(nodehandle.now(&host.hardware), Time::new(201, 520_000_000));
+    }
+
+    #[test]
+    fn topics_must_fit_the_output_buffer() {
+        let mut led = Subscriber::new("the_status_led", |_: std_msgs::Bool| {});
+        let mut nodehandle: NodeHandle<'_, 4, 4, 128, 80> = NodeHandle::default();
+
+        // 72 bytes of payload leaves room for 9 bytes of topic name alongside "std_msgs/Bool"
+        assert!(nodehandle.advertise::<std_msgs::Bool>("status").is_ok());
+        assert!(matches!(nodehandle.advertise::<std_msgs::Bool>("status_led"), Err(NodeHandleError::MessageTooLarge)));
+        assert!(matches!(nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut led), Err(NodeHandleError::MessageTooLarge)));
+
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+        assert!(host.topic(rosserial_msgs::TOPICINFO_ID_PUBLISHER, "status").is_some());
+        assert_eq!(host.topics().len(), 1);
+    }
+
+    #[test]
+    fn sync_timeout_disconnects() {
+        let states = RefCell::new(Vec::new());
+        let mut on_state = |state| states.borrow_mut().push(state);
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.set_state_callback(&mut on_state);
+        nodehandle.set_sync_timeout(1000);
+
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+        assert_eq!(*states.borrow(), [ConnectionState::Negotiating, ConnectionState::Connected]);
+
+        // The host stops answering, the node keeps asking until the timeout
+        for _ in 0..10 {
+            host.hardware.advance(100);
+            nodehandle.spin_once(&mut host.hardware);
+        }
+        assert!(nodehandle.connected());
+        let requests = host.hardware.take_frames();
+        assert!(!requests.is_empty() && requests.iter().all(|f| f.topic == rosserial_msgs::TOPICINFO_ID_TIME));
+
+        host.hardware.advance(100);
+        nodehandle.spin_once(&mut host.hardware);
+        assert_eq!(nodehandle.connection_state(), ConnectionState::Disconnected);
+
+        assert!(host.connect(&mut nodehandle));
+        assert_eq!(*states.borrow(), [
+            ConnectionState::Negotiating,
+            ConnectionState::Connected,
+            ConnectionState::Disconnected,
+            ConnectionState::Negotiating,
+            ConnectionState::Connected,
+        ]);
+    }
+
+    #[test]
+    fn publish_and_subscribe() {
+        let received = Cell::new(0);
+        let mut led = Subscriber::new("led", |msg: std_msgs::Bool| {
+            if msg.data {
+                received.set(received.get() + 1);
+            }
+        });
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        let status = nodehandle.advertise::<std_msgs::UInt16>("status").unwrap();
+        nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut led).unwrap();
+
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+
+        host.publish("led", &std_msgs::Bool { data: true });
+        host.spin(&mut nodehandle);
+
+        nodehandle.publish(status, &std_msgs::UInt16 { data: 1234 }, &mut host.hardware).unwrap();
+        nodehandle.logwarn("hot", &mut host.hardware);
+        host.spin(&mut nodehandle);
+
+        assert_eq!(host.received::<std_msgs::UInt16>("status"), [std_msgs::UInt16 { data: 1234 }]);
+        assert_eq!(host.logs(), [(rosserial_msgs::LOG_WARN, "hot".to_string())]);
+        assert_eq!(received.get(), 1);
+    }
+
+    #[test]
+    fn corrupt_frames_are_counted_and_skipped() {
+        let received = Cell::new(0);
+        let mut led = Subscriber::new("led", |_: std_msgs::Bool| received.set(received.get() + 1));
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut led).unwrap();
+
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+
+        let id = host.topic(rosserial_msgs::TOPICINFO_ID_SUBSCRIBER, "led").unwrap().id;
+        let frame = Frame::message(id, &std_msgs::Bool { data: true }).to_bytes();
+
+        // Bad length checksum, then the next frame starts straight after
+        let mut corrupt = frame.clone();
+        corrupt[4] ^= 0x01;
+        host.hardware.feed(&corrupt);
+        host.hardware.feed(&frame);
+        host.spin(&mut nodehandle);
+
+        assert_eq!(nodehandle.stats().length_checksum_errors, 1);
+        assert_eq!(received.get(), 1);
+
+        // Bad payload byte, with noise before the next frame
+        let mut corrupt = frame.clone();
+        corrupt[7] ^= 0x02;
+        host.hardware.feed(&corrupt);
+        host.hardware.feed(&[0xFF, 0x00, 0xFE]);
+        host.hardware.feed(&frame);
+        host.spin(&mut nodehandle);
+
+        assert_eq!(nodehandle.stats().message_checksum_errors, 1);
+        assert_eq!(received.get(), 2);
+    }
+
+    #[test]
+    fn oversized_frames_are_skipped() {
+        let received = Cell::new(0);
+        let mut led = Subscriber::new("led", |_: std_msgs::Bool| received.set(received.get() + 1));
+        let mut nodehandle: NodeHandle<'_, 4, 4, 64, 128> = NodeHandle::default();
+        nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut led).unwrap();
+
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+
+        let id = host.topic(rosserial_msgs::TOPICINFO_ID_SUBSCRIBER, "led").unwrap().id;
+        let frame = Frame::message(id, &std_msgs::Bool { data: true }).to_bytes();
+
+        // Fill the oversized payload with frames, none of which may be dispatched
+        let payload: Vec<u8> = frame.iter().cycle().take(100).copied().collect();
+        host.send(&Frame::new(id, &payload));
+        host.hardware.feed(&frame);
+        host.spin(&mut nodehandle);
+
+        assert_eq!(nodehandle.stats().overflow_errors, 1);
+        assert_eq!(nodehandle.stats().message_checksum_errors, 0);
+        assert_eq!(received.get(), 1);
+    }
+
+    #[test]
+    fn host_connects() {
+        let received = Cell::new(false);
+        let mut led = Subscriber::new("led", |msg: std_msgs::Bool| received.set(msg.data));
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.advertise::<std_msgs::Bool>("status").unwrap();
+        nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut led).unwrap();
+
+        let mut device = MockHardware::new();
+        let mut host = Host::new(MockHardware::new());
+
+        assert_eq!(topic_requests(&exchange(&mut host, &mut nodehandle, &mut device)), 1);
+        assert!(host.connected() && nodehandle.connected());
+        assert_eq!(host.publishers()[0].name, "status");
+        assert_eq!(host.subscribers()[0].name, "led");
+
+        host.publish("led", &std_msgs::Bool { data: true }).unwrap();
+        exchange(&mut host, &mut nodehandle, &mut device);
+        assert!(received.get());
+
+        // The device keeps syncing, so the topics are not requested again
+        for _ in 0..60 {
+            host.hardware().advance(1000);
+            device.advance(1000);
+            assert_eq!(topic_requests(&exchange(&mut host, &mut nodehandle, &mut device)), 0);
+        }
+        assert!(host.connected() && nodehandle.connected());
+    }
+
+    #[test]
+    fn host_reconnects_after_device_reset() {
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.advertise::<std_msgs::Bool>("status").unwrap();
+
+        let mut device = MockHardware::new();
+        let mut host = Host::new(MockHardware::new());
+        exchange(&mut host, &mut nodehandle, &mut device);
+        assert!(host.connected());
+
+        // After a reset the device waits silently for a topic request
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.advertise::<std_msgs::UInt16>("count").unwrap();
+
+        for _ in 0..15 {
+            host.hardware().advance(1000);
+            assert_eq!(topic_requests(&exchange(&mut host, &mut nodehandle, &mut device)), 0);
+        }
+        assert!(host.connected() && !nodehandle.connected());
+
+        host.hardware().advance(1000);
+        assert_eq!(topic_requests(&exchange(&mut host, &mut nodehandle, &mut device)), 1);
+        assert!(host.connected() && nodehandle.connected());
+        assert_eq!(host.publishers().len(), 1);
+        assert_eq!(host.publishers()[0].name, "count");
+    }
+
+    #[test]
+    fn params() {
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+
+        host.send(&Frame::new(rosserial_msgs::TOPICINFO_ID_PARAMETER_REQUEST, &int_params(&[3, -4])));
+        let mut values = [0; 2];
+        nodehandle.get_param_ints("gains", &mut values, &mut host.hardware, 100).unwrap();
+        assert_eq!(values, [3, -4]);
+
+        host.spin(&mut nodehandle);
+        let request = host.take_received().pop().unwrap();
+        assert_eq!(request.topic, rosserial_msgs::TOPICINFO_ID_PARAMETER_REQUEST);
+        assert_eq!(rosserial_msgs::RequestParamRequest::parse(&request.payload).unwrap().name, "gains");
+
+        // A missing reply times out
+        host.hardware.set_time_step(10);
+        assert!(nodehandle.get_param_int("missing", &mut host.hardware, 100).is_err());
+    }
+
+    #[test]
+    fn service_round_trip() {
+        let mut server = ServiceServer::<AddOne, _>::new("add_one", |request, response| {
+            response.data = request.data + 1;
+        });
+        let mut nodehandle: NodeHandle = NodeHandle::default();
+        nodehandle.register_service_server(&mut server).unwrap();
+        let client = nodehandle.service_client::<AddOne>("add_two").unwrap();
+
+        let mut host = MockHost::new();
+        assert!(host.connect(&mut nodehandle));
+
+        // Host calling the node
+        let request_id = host.topic(rosserial_msgs::TOPICINFO_ID_SERVICE_SERVER + rosserial_msgs::TOPICINFO_ID_SUBSCRIBER, "add_one").unwrap().id;
+        let response_id = host.topic(rosserial_msgs::TOPICINFO_ID_SERVICE_SERVER + rosserial_msgs::TOPICINFO_ID_PUBLISHER, "add_one").unwrap().id;
+
+        host.send(&Frame::message(request_id, &std_msgs::Int32 { data: 41 }));
+        host.spin(&mut nodehandle);
+
+        let response = host.take_received().pop().unwrap();
+        assert_eq!(response.topic, response_id);
+        assert_eq!(response.decode::<std_msgs::Int32>().data, 42);
+
+        // Node calling the host
+        let request_id = host.topic(rosserial_msgs::TOPICINFO_ID_SERVICE_CLIENT + rosserial_msgs::TOPICINFO_ID_PUBLISHER, "add_two").unwrap().id;
+        let response_id = host.topic(rosserial_msgs::TOPICINFO_ID_SERVICE_CLIENT + rosserial_msgs::TOPICINFO_ID_SUBSCRIBER, "add_two").unwrap().id;
+
+        host.send(&Frame::message(response_id, &std_msgs::Int32 { data: 9 }));
+        let response = nodehandle.call(&client, &std_msgs::Int32 { data: 7 }, &mut host.hardware, 100).unwrap();
+        assert_eq!(response.data, 9);
+
+        host.spin(&mut nodehandle);
+        let request = host.take_received().pop().unwrap();
+        assert_eq!(request.topic, request_id);
+        assert_eq!(request.decode::<std_msgs::Int32>().data, 7);
+    }
+}
@@ -0,0 +1,176 @@
+//! Adapter for `embedded-hal` 0.2 serial ports
+
+use core::cell::UnsafeCell;
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use ::embedded_hal::serial;
+
+use crate::ros::HardwareInterface;
+
+/// `HardwareInterface` over the split halves of an `embedded-hal` 0.2 serial port.
+///
+/// Each byte is written with a busy wait for the transmitter. `clock` returns milliseconds, and may wrap.
+///
+/// ```ignore
+/// let (tx, rx) = serial.split();
+/// let mut hardware = SerialHardware::new(rx, tx, || millis());
+///
+/// loop {
+///     nodehandle.spin_once(&mut hardware);
+/// }
+/// ```
+pub struct SerialHardware<Rx, Tx, C> {
+    rx: Rx,
+    tx: Tx,
+    clock: C,
+}
+
+impl<Rx, Tx, C> SerialHardware<Rx, Tx, C>
+where
+    Rx: serial::Read<u8>,
+    Tx: serial::Write<u8>,
+    C: Fn() -> u32,
+{
+    pub fn new(rx: Rx, tx: Tx, clock: C) -> Self {
+        SerialHardware {
+            rx,
+            tx,
+            clock,
+        }
+    }
+
+    pub fn free(self) -> (Rx, Tx, C) {
+        (self.rx, self.tx, self.clock)
+    }
+}
+
+impl<Rx, Tx, C> HardwareInterface for SerialHardware<Rx, Tx, C>
+where
+    Rx: serial::Read<u8>,
+    Tx: serial::Write<u8>,
+    C: Fn() -> u32,
+{
+    fn read(&mut self) -> Option<u8> {
+        // An overrun loses bytes, which the node sees as a frame with a bad checksum and skips
+        self.rx.read().ok()
+    }
+
+    fn write(&mut self, data: u8) {
+        nb::block!(self.tx.write(data)).ok();
+    }
+
+    fn time(&self) -> u32 {
+        (self.clock)()
+    }
+
+    fn flush(&mut self) {
+        nb::block!(self.tx.flush()).ok();
+    }
+}
+
+/// Receive ring buffer, filled from the serial interrupt and drained by `spin_once`.
+///
+/// Split it into a producer for the interrupt handler and a consumer to pass to `SerialHardware`
+/// as the receiver:
+///
+/// ```ignore
+/// static mut RX_BUFFER: RxBuffer<512> = RxBuffer::new();
+///
+/// let (producer, consumer) = unsafe { RX_BUFFER.split() };
+/// let mut hardware = SerialHardware::new(consumer, tx, || millis());
+///
+/// // USART interrupt
+/// producer.poll(&mut rx);
+/// ```
+pub struct RxBuffer<const N: usize> {
+    buffer: UnsafeCell<[u8; N]>,
+    write_idx: AtomicUsize,
+    read_idx: AtomicUsize,
+}
+
+// The producer and consumer only touch their own end of the buffer
+unsafe impl<const N: usize> Sync for RxBuffer<N> {}
+
+impl<const N: usize> RxBuffer<N> {
+    pub const fn new() -> Self {
+        RxBuffer {
+            buffer: UnsafeCell::new([0; N]),
+            write_idx: AtomicUsize::new(0),
+            read_idx: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn split(&mut self) -> (RxProducer<'_, N>, RxConsumer<'_, N>) {
+        (RxProducer { buffer: self }, RxConsumer { buffer: self })
+    }
+}
+
+impl<const N: usize> Default for RxBuffer<N> {
+    fn default() -> Self {
+        RxBuffer::new()
+    }
+}
+
+/// Interrupt side of an `RxBuffer`
+pub struct RxProducer<'b, const N: usize> {
+    buffer: &'b RxBuffer<N>,
+}
+
+unsafe impl<const N: usize> Send for RxProducer<'_, N> {}
+
+impl<const N: usize> RxProducer<'_, N> {
+    /// Add a byte to the buffer, returning false if the buffer is full and the byte was dropped
+    pub fn push(&mut self, data: u8) -> bool {
+        let write_idx = self.buffer.write_idx.load(Ordering::Relaxed);
+        let next = (write_idx + 1) % N;
+
+        if next == self.buffer.read_idx.load(Ordering::Acquire) {
+            return false;
+        }
+
+        // Safe as only the producer writes to the slot at `write_idx`, and the consumer does not read it until published
+        unsafe { (*self.buffer.buffer.get())[write_idx] = data };
+        self.buffer.write_idx.store(next, Ordering::Release);
+
+        true
+    }
+
+    /// Move all available bytes from the serial receiver into the buffer
+    pub fn poll<Rx: serial::Read<u8>>(&mut self, rx: &mut Rx) {
+        while let Ok(data) = rx.read() {
+            self.push(data);
+        }
+    }
+}
+
+/// Main loop side of an `RxBuffer`, usable as the receiver of a `SerialHardware`
+pub struct RxConsumer<'b, const N: usize> {
+    buffer: &'b RxBuffer<N>,
+}
+
+unsafe impl<const N: usize> Send for RxConsumer<'_, N> {}
+
+impl<const N: usize> RxConsumer<'_, N> {
+    pub fn pop(&mut self) -> Option<u8> {
+        let read_idx = self.buffer.read_idx.load(Ordering::Relaxed);
+
+        if read_idx == self.buffer.write_idx.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // Safe as the producer does not write to this slot until `read_idx` moves past it
+        let data = unsafe { (*self.buffer.buffer.get())[read_idx] };
+        self.buffer.read_idx.store((read_idx + 1) % N, Ordering::Release);
+
+        Some(data)
+    }
+}
+
+impl<const N: usize> serial::Read<u8> for RxConsumer<'_, N> {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.pop().ok_or(nb::Error::WouldBlock)
+    }
+}
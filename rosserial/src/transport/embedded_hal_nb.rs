@@ -0,0 +1,57 @@
+//! Adapter for `embedded-hal-nb` 1.0 serial ports
+
+use ::embedded_hal_nb::serial;
+
+use crate::ros::HardwareInterface;
+
+/// `HardwareInterface` over `embedded-hal-nb` serial halves, for HALs on `embedded-hal` 1.0.
+///
+/// This is the equivalent of `transport::embedded_hal::SerialHardware`, taking the same millisecond clock.
+pub struct NbSerialHardware<Rx, Tx, C> {
+    rx: Rx,
+    tx: Tx,
+    clock: C,
+}
+
+impl<Rx, Tx, C> NbSerialHardware<Rx, Tx, C>
+where
+    Rx: serial::Read<u8>,
+    Tx: serial::Write<u8>,
+    C: Fn() -> u32,
+{
+    pub fn new(rx: Rx, tx: Tx, clock: C) -> Self {
+        NbSerialHardware {
+            rx,
+            tx,
+            clock,
+        }
+    }
+
+    pub fn free(self) -> (Rx, Tx, C) {
+        (self.rx, self.tx, self.clock)
+    }
+}
+
+impl<Rx, Tx, C> HardwareInterface for NbSerialHardware<Rx, Tx, C>
+where
+    Rx: serial::Read<u8>,
+    Tx: serial::Write<u8>,
+    C: Fn() -> u32,
+{
+    fn read(&mut self) -> Option<u8> {
+        // `WouldBlock` is simply no data. Other `ErrorKind`s have no recovery beyond resyncing on the next frame
+        self.rx.read().ok()
+    }
+
+    fn write(&mut self, data: u8) {
+        nb::block!(self.tx.write(data)).ok();
+    }
+
+    fn time(&self) -> u32 {
+        (self.clock)()
+    }
+
+    fn flush(&mut self) {
+        nb::block!(self.tx.flush()).ok();
+    }
+}
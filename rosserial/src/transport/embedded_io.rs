@@ -0,0 +1,71 @@
+//! Adapter for `embedded-io` readers and writers
+
+use ::embedded_io::{Read, ReadReady, Write};
+
+use super::read_byte;
+use crate::ros::HardwareInterface;
+
+/// `HardwareInterface` over `embedded-io` streams, such as the UART or USB CDC drivers of most HALs.
+///
+/// The receiver must implement `ReadReady` so `spin_once` never blocks waiting for data. Streams carry no
+/// notion of time, so `clock` gives the node milliseconds from e.g. a timer peripheral.
+pub struct IoHardware<Rx, Tx, C> {
+    rx: Rx,
+    tx: Tx,
+    clock: C,
+}
+
+impl<Rx, Tx, C> IoHardware<Rx, Tx, C>
+where
+    Rx: Read + ReadReady,
+    Tx: Write,
+    C: Fn() -> u32,
+{
+    pub fn new(rx: Rx, tx: Tx, clock: C) -> Self {
+        IoHardware {
+            rx,
+            tx,
+            clock,
+        }
+    }
+
+    pub fn free(self) -> (Rx, Tx, C) {
+        (self.rx, self.tx, self.clock)
+    }
+}
+
+impl<Rx, Tx, C> HardwareInterface for IoHardware<Rx, Tx, C>
+where
+    Rx: Read + ReadReady,
+    Tx: Write,
+    C: Fn() -> u32,
+{
+    fn read(&mut self) -> Option<u8> {
+        read_byte(self)
+    }
+
+    fn write(&mut self, data: u8) {
+        self.write_all(&[data]);
+    }
+
+    fn time(&self) -> u32 {
+        (self.clock)()
+    }
+
+    fn read_into(&mut self, buf: &mut [u8]) -> usize {
+        if buf.is_empty() || !self.rx.read_ready().unwrap_or(false) {
+            return 0;
+        }
+
+        // The driver has already discarded whatever the error cost, so carry on with the next read
+        self.rx.read(buf).unwrap_or(0)
+    }
+
+    fn write_all(&mut self, data: &[u8]) {
+        self.tx.write_all(data).ok();
+    }
+
+    fn flush(&mut self) {
+        self.tx.flush().ok();
+    }
+}
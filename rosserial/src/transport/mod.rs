@@ -0,0 +1,32 @@
+//! `HardwareInterface` implementations for common transports, each behind a cargo feature
+
+pub mod udp;
+
+#[cfg(feature = "embedded-hal")]
+pub mod embedded_hal;
+
+#[cfg(feature = "embedded-hal-nb")]
+pub mod embedded_hal_nb;
+
+#[cfg(feature = "embedded-io")]
+pub mod embedded_io;
+
+#[cfg(feature = "std")]
+pub mod tcp;
+
+#[cfg(feature = "serialport")]
+pub mod serial;
+
+use crate::ros::HardwareInterface;
+
+/// `HardwareInterface::read` for transports that read in blocks through `read_into`
+pub(crate) fn read_byte<H: HardwareInterface>(hardware: &mut H) -> Option<u8> {
+    let mut data = [0u8];
+
+    if hardware.read_into(&mut data) == 1 {
+        Some(data[0])
+    }
+    else {
+        None
+    }
+}
@@ -0,0 +1,129 @@
+//! Serial port and pseudo-terminal transport, for running a node as a process talking to
+//! `rosserial_python`'s `serial_node.py`
+
+use std::boxed::Box;
+use std::time::{Duration, Instant};
+
+use ::serialport::SerialPort;
+#[cfg(unix)]
+use ::serialport::TTYPort;
+
+use super::read_byte;
+use crate::ros::HardwareInterface;
+
+/// Baud rate `serial_node.py` uses unless told otherwise
+pub const DEFAULT_BAUD_RATE: u32 = 57600;
+
+// Writes block for at most this long, reads never block
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// `HardwareInterface` over a serial port from the `serialport` crate. Ports are opened in raw mode.
+pub struct SerialPortHardware {
+    port: Box<dyn SerialPort>,
+    start: Instant,
+}
+
+impl SerialPortHardware {
+    /// Open a tty device, e.g. `SerialPortHardware::open("/dev/ttyUSB0", DEFAULT_BAUD_RATE)`
+    pub fn open(path: &str, baud_rate: u32) -> ::serialport::Result<Self> {
+        let port = ::serialport::new(path, baud_rate)
+            .timeout(WRITE_TIMEOUT)
+            .open()?;
+
+        Ok(Self::new(port))
+    }
+
+    /// Use a port that has already been opened and configured
+    pub fn new(mut port: Box<dyn SerialPort>) -> Self {
+        port.set_timeout(WRITE_TIMEOUT).ok();
+
+        SerialPortHardware {
+            port,
+            start: Instant::now(),
+        }
+    }
+
+    /// Create a pseudo-terminal pair, returning the node's end and the other end.
+    ///
+    /// `serial_node.py` can be pointed at the other end's `name()`, or a test can talk to it directly.
+    /// The other end must stay open while the node is in use.
+    #[cfg(unix)]
+    pub fn pty() -> ::serialport::Result<(Self, TTYPort)> {
+        let (node, other) = TTYPort::pair()?;
+        Ok((Self::new(Box::new(node)), other))
+    }
+
+    pub fn free(self) -> Box<dyn SerialPort> {
+        self.port
+    }
+}
+
+impl HardwareInterface for SerialPortHardware {
+    fn read(&mut self) -> Option<u8> {
+        read_byte(self)
+    }
+
+    fn write(&mut self, data: u8) {
+        self.write_all(&[data]);
+    }
+
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn read_into(&mut self, buf: &mut [u8]) -> usize {
+        let available = self.port.bytes_to_read().unwrap_or(0) as usize;
+        if available == 0 || buf.is_empty() {
+            return 0;
+        }
+
+        let n = available.min(buf.len());
+
+        // A port that keeps failing, e.g. an unplugged adapter, reads as silence until the node loses sync
+        self.port.read(&mut buf[..n]).unwrap_or(0)
+    }
+
+    fn write_all(&mut self, data: &[u8]) {
+        self.port.write_all(data).ok();
+    }
+
+    fn flush(&mut self) {
+        self.port.flush().ok();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::msgs::{rosserial_msgs, std_msgs};
+    use crate::ros::write_frame;
+
+    const TOPIC_REQUEST: [u8; 8] = [0xFF, 0xFE, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF];
+
+    #[test]
+    fn pty_round_trip() {
+        let (mut hardware, mut other) = SerialPortHardware::pty().unwrap();
+        other.set_timeout(Duration::from_secs(1)).unwrap();
+
+        write_frame(rosserial_msgs::TOPICINFO_ID_PUBLISHER, &std_msgs::Empty, 0, &mut hardware);
+        let mut frame = [0u8; 8];
+        other.read_exact(&mut frame).unwrap();
+        assert_eq!(frame, TOPIC_REQUEST);
+
+        other.write_all(&TOPIC_REQUEST).unwrap();
+
+        let start = Instant::now();
+        let mut received = Vec::new();
+        while received.len() < TOPIC_REQUEST.len() {
+            assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+
+            let mut buf = [0u8; 8];
+            let n = hardware.read_into(&mut buf);
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(received, TOPIC_REQUEST);
+    }
+}
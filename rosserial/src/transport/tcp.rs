@@ -0,0 +1,253 @@
+//! TCP client transport for `rosserial_server`'s `socket_node` and `rosserial_python`'s tcp mode
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use super::read_byte;
+use crate::ros::HardwareInterface;
+
+/// Port the rosserial host nodes listen on by default
+pub const DEFAULT_PORT: u16 = 11411;
+
+const DEFAULT_RECONNECT_INTERVAL: u32 = 1000;
+const DEFAULT_CONNECT_TIMEOUT: u32 = 1000;
+const DEFAULT_WRITE_TIMEOUT: u32 = 1000;
+
+/// `HardwareInterface` over a TCP connection to a rosserial host.
+///
+/// Reads return immediately while connected. If the connection drops it is re-established from `read`,
+/// at most once per reconnect interval, and the host then renegotiates topics as it would for a new device.
+/// Each reconnect attempt blocks that read for up to the connect timeout, so keep it short if the spin loop
+/// has other work. Frames written while disconnected are dropped, and a write that does not complete within
+/// the write timeout, e.g. to a host that stopped reading, drops the connection.
+pub struct TcpHardware {
+    addr: SocketAddr,
+    stream: Option<TcpStream>,
+    tx: Vec<u8>,
+    start: Instant,
+    reconnect_interval: u32,
+    connect_timeout: u32,
+    write_timeout: u32,
+    last_attempt: u32,
+}
+
+impl TcpHardware {
+    /// Connect to a host, e.g. `TcpHardware::connect(("192.168.1.10", DEFAULT_PORT))`
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let addr = addr.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no address to connect to"))?;
+
+        let mut hardware = TcpHardware {
+            addr,
+            stream: None,
+            tx: Vec::new(),
+            start: Instant::now(),
+            reconnect_interval: DEFAULT_RECONNECT_INTERVAL,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            last_attempt: 0,
+        };
+
+        hardware.stream = Some(hardware.open()?);
+
+        Ok(hardware)
+    }
+
+    /// Set the minimum time between reconnect attempts (ms)
+    pub fn set_reconnect_interval(&mut self, interval: u32) {
+        self.reconnect_interval = interval;
+    }
+
+    /// Set how long a reconnect attempt may block a read (ms). Must be non-zero
+    pub fn set_connect_timeout(&mut self, timeout: u32) {
+        self.connect_timeout = timeout;
+    }
+
+    /// Set how long sending a frame may block before the connection is dropped (ms). Must be non-zero
+    pub fn set_write_timeout(&mut self, timeout: u32) {
+        self.write_timeout = timeout;
+
+        if let Some(stream) = self.stream.as_ref() {
+            stream.set_write_timeout(Some(Duration::from_millis(timeout as u64))).ok();
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn open(&self) -> io::Result<TcpStream> {
+        let stream = TcpStream::connect_timeout(&self.addr, Duration::from_millis(self.connect_timeout as u64))?;
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(Duration::from_millis(self.write_timeout as u64)))?;
+
+        Ok(stream)
+    }
+
+    fn reconnect(&mut self) {
+        let now = self.time();
+        if now.wrapping_sub(self.last_attempt) < self.reconnect_interval {
+            return;
+        }
+
+        self.last_attempt = now;
+        self.stream = self.open().ok();
+    }
+
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.last_attempt = self.time();
+    }
+}
+
+impl HardwareInterface for TcpHardware {
+    fn read(&mut self) -> Option<u8> {
+        read_byte(self)
+    }
+
+    fn write(&mut self, data: u8) {
+        self.tx.push(data);
+    }
+
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn read_into(&mut self, buf: &mut [u8]) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => {
+                self.reconnect();
+                return 0;
+            },
+        };
+
+        match stream.read(buf) {
+            // The host closed the connection
+            Ok(0) => {
+                self.disconnect();
+                0
+            },
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => 0,
+            Err(_) => {
+                self.disconnect();
+                0
+            },
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) {
+        self.tx.extend_from_slice(data);
+    }
+
+    /// Send the buffered frame in one write. This blocks until the frame is sent, or the write timeout drops
+    /// the connection
+    fn flush(&mut self) {
+        let tx = &self.tx;
+        let result = match self.stream.as_mut() {
+            Some(stream) => {
+                stream.set_nonblocking(false)
+                    .and_then(|_| stream.write_all(tx))
+                    .and_then(|_| stream.set_nonblocking(true))
+            },
+            None => Ok(()),
+        };
+
+        self.tx.clear();
+
+        // Includes timing out, which may have left part of a frame on the stream
+        if result.is_err() {
+            self.disconnect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::vec;
+
+    use super::*;
+    use crate::msgs::{rosserial_msgs, std_msgs};
+    use crate::ros::write_frame;
+
+    const TOPIC_REQUEST: [u8; 8] = [0xFF, 0xFE, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF];
+
+    /// Run `f` until it returns true, failing the test after a second
+    fn wait_for<F: FnMut() -> bool>(mut f: F) {
+        let start = Instant::now();
+        while !f() {
+            assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn send_and_receive(hardware: &mut TcpHardware, listener: &TcpListener) -> TcpStream {
+        let (mut peer, _) = listener.accept().unwrap();
+
+        write_frame(rosserial_msgs::TOPICINFO_ID_PUBLISHER, &std_msgs::Empty, 0, hardware);
+        let mut frame = [0u8; 8];
+        peer.read_exact(&mut frame).unwrap();
+        assert_eq!(frame, TOPIC_REQUEST);
+
+        peer.write_all(&TOPIC_REQUEST).unwrap();
+        let mut received = Vec::new();
+        wait_for(|| {
+            let mut buf = [0u8; 8];
+            let n = hardware.read_into(&mut buf);
+            received.extend_from_slice(&buf[..n]);
+            received.len() == TOPIC_REQUEST.len()
+        });
+        assert_eq!(received, TOPIC_REQUEST);
+
+        peer
+    }
+
+    #[test]
+    fn reconnects_after_the_host_goes_away() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut hardware = TcpHardware::connect(addr).unwrap();
+        hardware.set_reconnect_interval(0);
+        let peer = send_and_receive(&mut hardware, &listener);
+
+        drop(peer);
+        drop(listener);
+        wait_for(|| {
+            hardware.read_into(&mut [0u8; 8]);
+            !hardware.is_connected()
+        });
+
+        let listener = TcpListener::bind(addr).unwrap();
+        wait_for(|| {
+            hardware.read_into(&mut [0u8; 8]);
+            hardware.is_connected()
+        });
+        send_and_receive(&mut hardware, &listener);
+    }
+
+    #[test]
+    fn write_timeout_disconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let mut hardware = TcpHardware::connect(listener.local_addr().unwrap()).unwrap();
+        hardware.set_write_timeout(50);
+        let (_peer, _) = listener.accept().unwrap();
+
+        // The peer never reads, so this fills the socket buffers and times out
+        hardware.write_all(&vec![0u8; 64 << 20]);
+        hardware.flush();
+
+        assert!(!hardware.is_connected());
+    }
+}
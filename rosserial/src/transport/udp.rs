@@ -0,0 +1,205 @@
+//! Datagram transport for `rosserial_server`'s `udp_socket_node`.
+//!
+//! Each outgoing frame is sent as one datagram. Received datagrams are fed to the parser in order,
+//! so frames split across datagrams or several frames in one datagram are both handled.
+
+use super::read_byte;
+use crate::ros::HardwareInterface;
+
+/// Port `udp_socket_node` uses for both ends by default
+pub const DEFAULT_PORT: u16 = 11411;
+
+/// A datagram socket connected to the rosserial host. Implement this for a no_std UDP stack
+pub trait DatagramSocket {
+    /// Receive one datagram into `buf` without blocking, returning its length if one was waiting.
+    /// Datagrams longer than `buf` may be truncated.
+    fn receive(&mut self, buf: &mut [u8]) -> Option<usize>;
+    /// Send `data` as one datagram
+    fn send(&mut self, data: &[u8]);
+}
+
+/// `HardwareInterface` collecting each frame into one datagram on a `DatagramSocket`.
+///
+/// Network stacks rarely expose a clock of their own, so `clock` supplies the node's time in milliseconds.
+///
+/// `RX_BUF` must fit the largest datagram the host sends, `TX_BUF` the largest frame the node sends.
+/// Frames that do not fit in `TX_BUF` are dropped.
+pub struct UdpHardware<S, C, const RX_BUF: usize = 512, const TX_BUF: usize = 512> {
+    socket: S,
+    clock: C,
+
+    rx: [u8; RX_BUF],
+    rx_index: usize,
+    rx_len: usize,
+
+    tx: [u8; TX_BUF],
+    tx_len: usize,
+    tx_overflow: bool,
+}
+
+impl<S, C, const RX_BUF: usize, const TX_BUF: usize> UdpHardware<S, C, RX_BUF, TX_BUF>
+where
+    S: DatagramSocket,
+    C: Fn() -> u32,
+{
+    pub fn new(socket: S, clock: C) -> Self {
+        UdpHardware {
+            socket,
+            clock,
+
+            rx: [0; RX_BUF],
+            rx_index: 0,
+            rx_len: 0,
+
+            tx: [0; TX_BUF],
+            tx_len: 0,
+            tx_overflow: false,
+        }
+    }
+
+    pub fn free(self) -> (S, C) {
+        (self.socket, self.clock)
+    }
+}
+
+impl<S, C, const RX_BUF: usize, const TX_BUF: usize> HardwareInterface for UdpHardware<S, C, RX_BUF, TX_BUF>
+where
+    S: DatagramSocket,
+    C: Fn() -> u32,
+{
+    fn read(&mut self) -> Option<u8> {
+        read_byte(self)
+    }
+
+    fn write(&mut self, data: u8) {
+        self.write_all(&[data]);
+    }
+
+    fn time(&self) -> u32 {
+        (self.clock)()
+    }
+
+    fn read_into(&mut self, buf: &mut [u8]) -> usize {
+        if self.rx_index == self.rx_len {
+            self.rx_index = 0;
+            self.rx_len = self.socket.receive(&mut self.rx).unwrap_or(0).min(RX_BUF);
+        }
+
+        let n = (self.rx_len - self.rx_index).min(buf.len());
+        buf[..n].copy_from_slice(&self.rx[self.rx_index..self.rx_index + n]);
+        self.rx_index += n;
+
+        n
+    }
+
+    fn write_all(&mut self, data: &[u8]) {
+        if self.tx_len + data.len() > TX_BUF {
+            self.tx_overflow = true;
+            return;
+        }
+
+        self.tx[self.tx_len..self.tx_len + data.len()].copy_from_slice(data);
+        self.tx_len += data.len();
+    }
+
+    /// Send the buffered frame as one datagram
+    fn flush(&mut self) {
+        if !self.tx_overflow && self.tx_len > 0 {
+            self.socket.send(&self.tx[..self.tx_len]);
+        }
+
+        self.tx_len = 0;
+        self.tx_overflow = false;
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::std_socket::*;
+
+#[cfg(feature = "std")]
+mod std_socket {
+    use std::io;
+    use std::net::{ToSocketAddrs, UdpSocket};
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    use super::{DatagramSocket, UdpHardware};
+
+    /// `UdpHardware` over a `std::net::UdpSocket`
+    pub type StdUdpHardware = UdpHardware<UdpSocket, fn() -> u32>;
+
+    impl StdUdpHardware {
+        /// Bind to `local` and exchange datagrams with the host at `remote`.
+        ///
+        /// `udp_socket_node` sends to `client_addr:client_port` and listens on `server_port`, which
+        /// default to 127.0.0.1:11411 and 11411, so a node on the same machine needs a different
+        /// `client_port`, e.g. bind 127.0.0.1:11412 and set `~client_port:=11412`.
+        pub fn bind<L: ToSocketAddrs, R: ToSocketAddrs>(local: L, remote: R) -> io::Result<Self> {
+            let socket = UdpSocket::bind(local)?;
+            socket.connect(remote)?;
+            socket.set_nonblocking(true)?;
+
+            Ok(UdpHardware::new(socket, millis))
+        }
+    }
+
+    impl DatagramSocket for UdpSocket {
+        fn receive(&mut self, buf: &mut [u8]) -> Option<usize> {
+            // Errors (would block, or the host not listening yet) are treated as no data
+            self.recv(buf).ok()
+        }
+
+        fn send(&mut self, data: &[u8]) {
+            UdpSocket::send(self, data).ok();
+        }
+    }
+
+    fn millis() -> u32 {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_millis() as u32
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::net::UdpSocket;
+    use std::time::{Duration, Instant};
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::msgs::std_msgs;
+    use crate::ros::write_frame;
+
+    const TOPIC_REQUEST: [u8; 8] = [0xFF, 0xFE, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF];
+
+    #[test]
+    fn one_frame_per_datagram() {
+        let host = UdpSocket::bind("127.0.0.1:0").unwrap();
+        host.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+        let mut hardware = StdUdpHardware::bind("127.0.0.1:0", host.local_addr().unwrap()).unwrap();
+        host.connect(hardware.socket.local_addr().unwrap()).unwrap();
+
+        write_frame(100, &std_msgs::UInt16 { data: 0x0102 }, 2, &mut hardware);
+        write_frame(100, &std_msgs::UInt16 { data: 0x0102 }, 2, &mut hardware);
+
+        let mut buf = [0u8; 64];
+        for _ in 0..2 {
+            let n = host.recv(&mut buf).unwrap();
+            assert_eq!(buf[..n], [0xFF, 0xFE, 0x02, 0x00, 0xFD, 100, 0x00, 0x02, 0x01, 152]);
+        }
+
+        // Both frames in one datagram are read in order
+        host.send(&[TOPIC_REQUEST, TOPIC_REQUEST].concat()).unwrap();
+
+        let start = Instant::now();
+        let mut received = Vec::new();
+        while received.len() < 2 * TOPIC_REQUEST.len() {
+            assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+
+            let n = hardware.read_into(&mut buf[..5]);
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(received, [TOPIC_REQUEST, TOPIC_REQUEST].concat());
+    }
+}
@@ -37,7 +37,7 @@ impl<'a> SpinInstance<'a> {
 impl HardwareInterface for SpinInstance<'_> {
     fn read(&mut self) -> Option<u8> {
         // self.rx.read()
-        self.data
+        self.data.take()
     }
 
     fn write(&mut self, data: u8) {
@@ -95,9 +95,9 @@ const APP: () = {
             cmd_in.enqueue(msg.data).unwrap();
         });
 
-        let mut nodehandle = NodeHandle::default();
+        let mut nodehandle: NodeHandle = NodeHandle::default();
         let test_pub = nodehandle.advertise::<std_msgs::Bool>("test").unwrap();
-        nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut bool_sub);
+        nodehandle.register_subscriber::<_, std_msgs::Bool>(&mut bool_sub).unwrap();
 
         loop {
             let current_time = Instant::now();
@@ -118,7 +118,7 @@ const APP: () = {
                 let mut msg = std_msgs::Bool::default();
                 msg.data = true;
 
-                nodehandle.publish(test_pub, &msg, &mut spin_data);
+                nodehandle.publish(test_pub, &msg, &mut spin_data).ok();
                 last_pub = current_time;
             }
 